@@ -0,0 +1,259 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{stdout, BufWriter, Write},
+    process::ExitCode,
+};
+
+use physics::{
+    physics_system::PhysicsSystem, scene::Scene, simulation_config::SimulationFileConfig,
+    simulator::Simulator,
+};
+use serde::Serialize;
+
+const USAGE: &str = "Usage: headless <scene.toml> [--config <config.toml>] [--steps <n>] \
+[--delta <seconds>] [--format json|csv] [--trajectory] [--output <file>]";
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+const DEFAULT_STEPS: usize = 1000;
+const DEFAULT_DELTA: f32 = 1.0 / 240.0;
+const EXIT_CODE_ERROR: u8 = 1;
+const EXIT_CODE_NAN: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Json,
+    Csv,
+}
+
+struct Args {
+    scene_path: String,
+    config_path: String,
+    steps: usize,
+    delta: f32,
+    format: OutputFormat,
+    is_trajectory: bool,
+    output_path: Option<String>,
+}
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut scene_path = None;
+        let mut parsed = Self {
+            scene_path: String::new(),
+            config_path: DEFAULT_CONFIG_PATH.to_owned(),
+            steps: DEFAULT_STEPS,
+            delta: DEFAULT_DELTA,
+            format: OutputFormat::Json,
+            is_trajectory: false,
+            output_path: None,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value_of = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))
+            };
+            match arg.as_str() {
+                "--config" => parsed.config_path = value_of("--config")?,
+                "--steps" => {
+                    parsed.steps = value_of("--steps")?
+                        .parse()
+                        .map_err(|err| format!("Invalid '--steps': {err}"))?
+                }
+                "--delta" => {
+                    parsed.delta = value_of("--delta")?
+                        .parse()
+                        .map_err(|err| format!("Invalid '--delta': {err}"))?
+                }
+                "--format" => {
+                    parsed.format = match value_of("--format")?.as_str() {
+                        "json" => OutputFormat::Json,
+                        "csv" => OutputFormat::Csv,
+                        other => return Err(format!("Unknown format '{other}'")),
+                    }
+                }
+                "--trajectory" => parsed.is_trajectory = true,
+                "--output" => parsed.output_path = Some(value_of("--output")?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option '{arg}'")),
+                _ if scene_path.is_none() => scene_path = Some(arg),
+                _ => return Err(format!("Unexpected argument '{arg}'")),
+            }
+        }
+
+        parsed.scene_path = scene_path.ok_or("Missing scene file")?;
+        if !(parsed.delta > 0.0 && parsed.delta.is_finite()) {
+            return Err("'--delta' must be positive and finite".to_owned());
+        }
+
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PointState {
+    id: u64,
+    location: [f32; 2],
+    velocity: [f32; 2],
+}
+
+#[derive(Debug, Serialize)]
+struct Frame {
+    step: usize,
+    constraints: usize,
+    points: Vec<PointState>,
+}
+impl Frame {
+    fn new(step: usize, physics_system: &PhysicsSystem) -> Self {
+        let points = physics_system
+            .get_sorted_ids()
+            .iter()
+            .map(|id| {
+                let point = &physics_system.get_points_ids()[id];
+                PointState {
+                    id: *id,
+                    location: point.location.to_array(),
+                    velocity: point.velocity.to_array(),
+                }
+            })
+            .collect();
+
+        Self {
+            step,
+            constraints: physics_system.get_constraints().len(),
+            points,
+        }
+    }
+}
+
+fn has_nan(physics_system: &PhysicsSystem) -> bool {
+    physics_system
+        .get_points_ids()
+        .values()
+        .any(|point| !point.location.is_finite() || !point.velocity.is_finite())
+}
+
+/// Writes frames as they are produced, so a trajectory is never held in memory
+struct FrameWriter {
+    writer: BufWriter<Box<dyn Write>>,
+    format: OutputFormat,
+    is_trajectory: bool,
+    frames_written: usize,
+}
+impl FrameWriter {
+    fn new(args: &Args) -> Result<Self, Box<dyn Error>> {
+        let mut writer: BufWriter<Box<dyn Write>> = match &args.output_path {
+            Some(path) => BufWriter::new(Box::new(File::create(path)?)),
+            None => BufWriter::new(Box::new(stdout().lock())),
+        };
+        match args.format {
+            OutputFormat::Json if args.is_trajectory => write!(writer, "[")?,
+            OutputFormat::Json => {}
+            OutputFormat::Csv => writeln!(writer, "step,id,x,y,vx,vy")?,
+        }
+
+        Ok(Self {
+            writer,
+            format: args.format,
+            is_trajectory: args.is_trajectory,
+            frames_written: 0,
+        })
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<(), Box<dyn Error>> {
+        match self.format {
+            OutputFormat::Json => {
+                if self.frames_written > 0 {
+                    write!(self.writer, ",")?;
+                }
+                serde_json::to_writer(&mut self.writer, frame)?;
+            }
+            OutputFormat::Csv => {
+                for point in &frame.points {
+                    writeln!(
+                        self.writer,
+                        "{},{},{},{},{},{}",
+                        frame.step,
+                        point.id,
+                        point.location[0],
+                        point.location[1],
+                        point.velocity[0],
+                        point.velocity[1]
+                    )?;
+                }
+            }
+        }
+        self.frames_written += 1;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        if self.format == OutputFormat::Json && self.is_trajectory {
+            write!(self.writer, "]")?;
+        }
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+fn get_output_error(err: Box<dyn Error>) -> String {
+    format!("Error writing output: {err}")
+}
+
+/// Runs the simulation while writing out its frames, returning whether it produced NaNs
+fn run(args: &Args) -> Result<bool, Box<dyn Error>> {
+    let simulator: Simulator = SimulationFileConfig::load(&args.config_path)
+        .map_err(|err| format!("Error loading config file '{}': {err}", args.config_path))?
+        .into();
+    let (mut physics_system, _scene_params) = Scene::load(&args.scene_path)
+        .map_err(|err| format!("Error loading scene file '{}': {err}", args.scene_path))?
+        .into_parts();
+
+    let mut output = FrameWriter::new(args).map_err(get_output_error)?;
+    // Only the last frame is kept, the ones before it are written as soon as the next is ready
+    let mut frame = Frame::new(0, &physics_system);
+    let mut is_nan = false;
+    for step in 1..=args.steps {
+        if args.is_trajectory {
+            output.write(&frame).map_err(get_output_error)?;
+        }
+        simulator.next_step(&mut physics_system, args.delta);
+
+        is_nan = has_nan(&physics_system);
+        if args.is_trajectory || is_nan || step == args.steps {
+            frame = Frame::new(step, &physics_system);
+        }
+        if is_nan {
+            eprintln!("Simulation produced NaN at step {step}");
+            break;
+        }
+    }
+    output.write(&frame).map_err(get_output_error)?;
+    output.finish().map_err(get_output_error)?;
+
+    Ok(is_nan)
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            return ExitCode::from(EXIT_CODE_ERROR);
+        }
+    };
+
+    let is_nan = match run(&args) {
+        Ok(is_nan) => is_nan,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::from(EXIT_CODE_ERROR);
+        }
+    };
+
+    if is_nan {
+        ExitCode::from(EXIT_CODE_NAN)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,67 @@
+use macroquad::math::{vec2, Vec2};
+
+use crate::simulator::SimulationBoundingBox;
+
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 50.0;
+
+/// Maps world coordinates to screen pixels with a uniform scale, so circles stay round
+pub struct Camera {
+    center: Vec2,
+    zoom: f32,
+}
+impl Camera {
+    pub fn new(center: Vec2, zoom: f32) -> Self {
+        Self {
+            center,
+            zoom: zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+        }
+    }
+
+    /// Camera showing the whole bounding box in the shorter window side
+    pub fn fit_bounding_box(bounding_box: &SimulationBoundingBox) -> Self {
+        let width = bounding_box.max_x - bounding_box.min_x;
+        let height = bounding_box.max_y - bounding_box.min_y;
+
+        Self::new(
+            vec2(
+                (bounding_box.min_x + bounding_box.max_x) / 2.0,
+                (bounding_box.min_y + bounding_box.max_y) / 2.0,
+            ),
+            1.0 / width.max(height),
+        )
+    }
+
+    /// Screen pixels per world unit
+    pub fn get_scale(&self, screen_size: (f32, f32)) -> f32 {
+        screen_size.0.min(screen_size.1) * self.zoom
+    }
+
+    pub fn world_to_screen(&self, location: Vec2, screen_size: (f32, f32)) -> Vec2 {
+        (location - self.center) * self.get_scale(screen_size)
+            + vec2(screen_size.0, screen_size.1) / 2.0
+    }
+
+    pub fn screen_to_world(&self, location: Vec2, screen_size: (f32, f32)) -> Vec2 {
+        (location - vec2(screen_size.0, screen_size.1) / 2.0) / self.get_scale(screen_size)
+            + self.center
+    }
+
+    /// Converts a world length to pixels
+    pub fn world_to_screen_length(&self, length: f32, screen_size: (f32, f32)) -> f32 {
+        length * self.get_scale(screen_size)
+    }
+
+    /// Moves the view so the world follows a cursor displacement of `screen_delta` pixels
+    pub fn pan(&mut self, screen_delta: Vec2, screen_size: (f32, f32)) {
+        self.center -= screen_delta / self.get_scale(screen_size);
+    }
+
+    /// Zooms by `factor`, keeping the world point under `screen_location` fixed
+    pub fn zoom_at(&mut self, factor: f32, screen_location: Vec2, screen_size: (f32, f32)) {
+        let anchor = self.screen_to_world(screen_location, screen_size);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let moved_anchor = self.screen_to_world(screen_location, screen_size);
+        self.center += anchor - moved_anchor;
+    }
+}
@@ -5,106 +5,127 @@ use serde::{Deserialize, Serialize};
 use toml::from_str;
 
 use crate::{
-    controller::Controller,
+    controller::{Controller, EditorParams, TimeStepParams},
+    force_field::Falloff,
     physics_system::PhysicsSystem,
     renderer::{DrawParams, Renderer},
-    simulator::{SimulationBoundingBox, SimulationParams, Simulator},
+    simulation_config::{get_invalid_wall_error, BoundingBoxConfig, SimulationConfig},
+    simulator::Simulator,
     ui_renderer::{UiParams, UiRenderer},
 };
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SimulationConfig {
-    air_resistence: f32,
-    gravity: f32,
-    point_size: f32,
-    spring_coeff: f32,
-    damping: f32,
-    collision_force: f32,
-    push_from_sides_force: f32,
-}
-impl Into<SimulationParams> for SimulationConfig {
-    fn into(self) -> SimulationParams {
-        SimulationParams {
-            gravity: self.gravity,
-            air_resistence: self.air_resistence,
-            point_size: self.point_size,
-            spring_coeff: self.spring_coeff,
-            damping: self.damping,
-            collision_force: self.collision_force,
-            push_from_sides_force: self.push_from_sides_force,
-        }
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct BoundingBoxConfig {
-    max_x: f32,
-    max_y: f32,
-    min_x: f32,
-    min_y: f32,
-}
-impl Into<SimulationBoundingBox> for BoundingBoxConfig {
-    fn into(self) -> SimulationBoundingBox {
-        SimulationBoundingBox {
-            min_x: self.min_x,
-            max_x: self.max_x,
-            min_y: self.min_y,
-            max_y: self.max_y,
-        }
-    }
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 struct RendererConfig {
     bg_color: [u8; 4],
-    point_size: f32,
     line_size: f32,
     point_color: [u8; 4],
     point_border_color: [u8; 4],
     static_point_color: [u8; 4],
     line_color: [u8; 4],
     stressed_line_color: [u8; 4],
+    rod_line_color: [u8; 4],
+    bounding_box_color: [u8; 4],
+    hover_color: [u8; 4],
+    selection_color: [u8; 4],
+    obstacle_color: [u8; 4],
+    obstacle_fill_color: [u8; 4],
+    force_field_color: [u8; 4],
+    positive_charge_color: [u8; 4],
+    negative_charge_color: [u8; 4],
 }
-impl Into<DrawParams> for RendererConfig {
-    fn into(self) -> DrawParams {
+impl From<RendererConfig> for DrawParams {
+    fn from(value: RendererConfig) -> Self {
         DrawParams {
             bg_color: Color::from_rgba(
-                self.bg_color[0],
-                self.bg_color[1],
-                self.bg_color[2],
-                self.bg_color[3],
+                value.bg_color[0],
+                value.bg_color[1],
+                value.bg_color[2],
+                value.bg_color[3],
             ),
-            point_size: self.point_size,
-            line_size: self.line_size,
+            line_size: value.line_size,
             point_color: Color::from_rgba(
-                self.point_color[0],
-                self.point_color[1],
-                self.point_color[2],
-                self.point_color[3],
+                value.point_color[0],
+                value.point_color[1],
+                value.point_color[2],
+                value.point_color[3],
             ),
             point_border_color: Color::from_rgba(
-                self.point_border_color[0],
-                self.point_border_color[1],
-                self.point_border_color[2],
-                self.point_border_color[3],
+                value.point_border_color[0],
+                value.point_border_color[1],
+                value.point_border_color[2],
+                value.point_border_color[3],
             ),
             static_point_color: Color::from_rgba(
-                self.static_point_color[0],
-                self.static_point_color[1],
-                self.static_point_color[2],
-                self.static_point_color[3],
+                value.static_point_color[0],
+                value.static_point_color[1],
+                value.static_point_color[2],
+                value.static_point_color[3],
             ),
             line_color: Color::from_rgba(
-                self.line_color[0],
-                self.line_color[1],
-                self.line_color[2],
-                self.line_color[3],
+                value.line_color[0],
+                value.line_color[1],
+                value.line_color[2],
+                value.line_color[3],
             ),
             stressed_line_color: Color::from_rgba(
-                self.stressed_line_color[0],
-                self.stressed_line_color[1],
-                self.stressed_line_color[2],
-                self.stressed_line_color[3],
+                value.stressed_line_color[0],
+                value.stressed_line_color[1],
+                value.stressed_line_color[2],
+                value.stressed_line_color[3],
+            ),
+            rod_line_color: Color::from_rgba(
+                value.rod_line_color[0],
+                value.rod_line_color[1],
+                value.rod_line_color[2],
+                value.rod_line_color[3],
+            ),
+            bounding_box_color: Color::from_rgba(
+                value.bounding_box_color[0],
+                value.bounding_box_color[1],
+                value.bounding_box_color[2],
+                value.bounding_box_color[3],
+            ),
+            hover_color: Color::from_rgba(
+                value.hover_color[0],
+                value.hover_color[1],
+                value.hover_color[2],
+                value.hover_color[3],
+            ),
+            selection_color: Color::from_rgba(
+                value.selection_color[0],
+                value.selection_color[1],
+                value.selection_color[2],
+                value.selection_color[3],
+            ),
+            obstacle_color: Color::from_rgba(
+                value.obstacle_color[0],
+                value.obstacle_color[1],
+                value.obstacle_color[2],
+                value.obstacle_color[3],
+            ),
+            obstacle_fill_color: Color::from_rgba(
+                value.obstacle_fill_color[0],
+                value.obstacle_fill_color[1],
+                value.obstacle_fill_color[2],
+                value.obstacle_fill_color[3],
+            ),
+            force_field_color: Color::from_rgba(
+                value.force_field_color[0],
+                value.force_field_color[1],
+                value.force_field_color[2],
+                value.force_field_color[3],
+            ),
+            positive_charge_color: Color::from_rgba(
+                value.positive_charge_color[0],
+                value.positive_charge_color[1],
+                value.positive_charge_color[2],
+                value.positive_charge_color[3],
+            ),
+            negative_charge_color: Color::from_rgba(
+                value.negative_charge_color[0],
+                value.negative_charge_color[1],
+                value.negative_charge_color[2],
+                value.negative_charge_color[3],
             ),
         }
     }
@@ -129,62 +150,114 @@ struct UiRendererConfig {
     speed_text_location: [f32; 2],
     speed_text_size: f32,
     speed_text_color: [u8; 4],
+    placement_text_location: [f32; 2],
+    tool_text_location: [f32; 2],
+    broken_line_color: [u8; 4],
+    broken_line_flash_time: f32,
 }
-impl Into<UiParams> for UiRendererConfig {
-    fn into(self) -> UiParams {
+impl From<UiRendererConfig> for UiParams {
+    fn from(value: UiRendererConfig) -> Self {
         UiParams {
-            paused_text_location: (self.paused_text_location[0], self.paused_text_location[1]),
-            paused_text_size: self.paused_text_size,
+            paused_text_location: (value.paused_text_location[0], value.paused_text_location[1]),
+            paused_text_size: value.paused_text_size,
             paused_text_color: Color::from_rgba(
-                self.paused_text_color[0],
-                self.paused_text_color[1],
-                self.paused_text_color[2],
-                self.paused_text_color[3],
+                value.paused_text_color[0],
+                value.paused_text_color[1],
+                value.paused_text_color[2],
+                value.paused_text_color[3],
             ),
-            line_size: self.line_size,
+            line_size: value.line_size,
             line_color: Color::from_rgba(
-                self.line_color[0],
-                self.line_color[1],
-                self.line_color[2],
-                self.line_color[3],
+                value.line_color[0],
+                value.line_color[1],
+                value.line_color[2],
+                value.line_color[3],
             ),
-            debug_text_location: (self.debug_text_location[0], self.debug_text_location[1]),
-            debug_text_size: self.debug_text_size,
+            debug_text_location: (value.debug_text_location[0], value.debug_text_location[1]),
+            debug_text_size: value.debug_text_size,
             debug_text_color: Color::from_rgba(
-                self.debug_text_color[0],
-                self.debug_text_color[1],
-                self.debug_text_color[2],
-                self.debug_text_color[3],
+                value.debug_text_color[0],
+                value.debug_text_color[1],
+                value.debug_text_color[2],
+                value.debug_text_color[3],
             ),
             debug_point_text_color: Color::from_rgba(
-                self.debug_point_text_color[0],
-                self.debug_point_text_color[1],
-                self.debug_point_text_color[2],
-                self.debug_point_text_color[3],
+                value.debug_point_text_color[0],
+                value.debug_point_text_color[1],
+                value.debug_point_text_color[2],
+                value.debug_point_text_color[3],
             ),
-            debug_point_text_size: self.debug_point_text_size,
+            debug_point_text_size: value.debug_point_text_size,
             debug_point_box_color: Color::from_rgba(
-                self.debug_point_box_color[0],
-                self.debug_point_box_color[1],
-                self.debug_point_box_color[2],
-                self.debug_point_box_color[3],
+                value.debug_point_box_color[0],
+                value.debug_point_box_color[1],
+                value.debug_point_box_color[2],
+                value.debug_point_box_color[3],
             ),
-            debug_point_velocity_line_size: self.debug_point_velocity_line_size,
+            debug_point_velocity_line_size: value.debug_point_velocity_line_size,
             debug_point_velocity_line_color: Color::from_rgba(
-                self.debug_point_velocity_line_color[0],
-                self.debug_point_velocity_line_color[1],
-                self.debug_point_velocity_line_color[2],
-                self.debug_point_velocity_line_color[3],
-            ),
-            debug_point_velocity_line_length: self.debug_point_velocity_line_length,
-            speed_text_location: (self.speed_text_location[0], self.speed_text_location[1]),
-            speed_text_size: self.speed_text_size,
+                value.debug_point_velocity_line_color[0],
+                value.debug_point_velocity_line_color[1],
+                value.debug_point_velocity_line_color[2],
+                value.debug_point_velocity_line_color[3],
+            ),
+            debug_point_velocity_line_length: value.debug_point_velocity_line_length,
+            speed_text_location: (value.speed_text_location[0], value.speed_text_location[1]),
+            speed_text_size: value.speed_text_size,
             speed_text_color: Color::from_rgba(
-                self.speed_text_color[0],
-                self.speed_text_color[1],
-                self.speed_text_color[2],
-                self.speed_text_color[3],
+                value.speed_text_color[0],
+                value.speed_text_color[1],
+                value.speed_text_color[2],
+                value.speed_text_color[3],
+            ),
+            placement_text_location: (
+                value.placement_text_location[0],
+                value.placement_text_location[1],
             ),
+            tool_text_location: (value.tool_text_location[0], value.tool_text_location[1]),
+            broken_line_color: Color::from_rgba(
+                value.broken_line_color[0],
+                value.broken_line_color[1],
+                value.broken_line_color[2],
+                value.broken_line_color[3],
+            ),
+            broken_line_flash_time: value.broken_line_flash_time,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TimeStepConfig {
+    time_step: f32,
+    max_steps_per_frame: usize,
+}
+impl From<TimeStepConfig> for TimeStepParams {
+    fn from(value: TimeStepConfig) -> Self {
+        TimeStepParams {
+            time_step: value.time_step,
+            max_steps_per_frame: value.max_steps_per_frame,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EditorConfig {
+    history_depth: usize,
+    obstacle_restitution: f32,
+    obstacle_friction: f32,
+    force_field_strength: f32,
+    wind_strength: f32,
+    force_field_falloff: Falloff,
+}
+impl From<EditorConfig> for EditorParams {
+    fn from(value: EditorConfig) -> Self {
+        EditorParams {
+            history_depth: value.history_depth,
+            obstacle_restitution: value.obstacle_restitution,
+            obstacle_friction: value.obstacle_friction,
+            force_field_strength: value.force_field_strength,
+            wind_strength: value.wind_strength,
+            force_field_falloff: value.force_field_falloff,
         }
     }
 }
@@ -193,24 +266,40 @@ impl Into<UiParams> for UiRendererConfig {
 pub struct Config {
     bounding_box_config: BoundingBoxConfig,
     simulation_config: SimulationConfig,
+    time_step_config: TimeStepConfig,
+    editor_config: EditorConfig,
     renderer_config: RendererConfig,
     ui_renderer_config: UiRendererConfig,
 }
 impl Config {
     pub fn load(filepath: &str) -> Result<Self, Box<dyn Error>> {
-        Ok(from_str(&read_to_string(filepath)?)?)
+        let config: Self = from_str(&read_to_string(filepath)?)?;
+        let time_step = config.time_step_config.time_step;
+        if !(time_step > 0.0 && time_step.is_finite()) {
+            return Err(format!("'time_step' must be positive, got {time_step}").into());
+        }
+        if config.time_step_config.max_steps_per_frame == 0 {
+            return Err("'max_steps_per_frame' must be at least 1".into());
+        }
+        if let Some(side) = config.bounding_box_config.get_invalid_wall() {
+            return Err(get_invalid_wall_error(side).into());
+        }
+
+        Ok(config)
     }
 }
-impl Into<Controller> for Config {
-    fn into(self) -> Controller {
+impl From<Config> for Controller {
+    fn from(value: Config) -> Self {
         Controller::new(
             PhysicsSystem::new(),
             Simulator::new(
-                self.simulation_config.into(),
-                self.bounding_box_config.into(),
+                value.simulation_config.into(),
+                value.bounding_box_config.into(),
             ),
-            Renderer::new(self.renderer_config.into()),
-            UiRenderer::new(self.ui_renderer_config.into()),
+            Renderer::new(value.renderer_config.into()),
+            UiRenderer::new(value.ui_renderer_config.into()),
+            value.time_step_config.into(),
+            value.editor_config.into(),
         )
     }
 }
@@ -1,15 +1,75 @@
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BreakThreshold {
+    /// Relative elongation or compression, `|current - rest| / rest`, zero while slack
+    Strain(f32),
+    /// Magnitude of the spring force. Rods hold their length by projection and carry no
+    /// spring force, so they cannot break on force
+    Force(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConstraintKind {
+    /// Two-way Hooke spring
+    Spring,
+    /// Resists stretching only, slack when compressed
+    Rope,
+    /// Resists compression only
+    Strut,
+    /// Hard distance constraint solved by iterative projection
+    Rod,
+}
+impl ConstraintKind {
+    pub fn next(self) -> Self {
+        match self {
+            ConstraintKind::Spring => ConstraintKind::Rope,
+            ConstraintKind::Rope => ConstraintKind::Strut,
+            ConstraintKind::Strut => ConstraintKind::Rod,
+            ConstraintKind::Rod => ConstraintKind::Spring,
+        }
+    }
+
+    /// Whether a constraint stretched by `displacement` beyond its rest length carries no load
+    pub fn is_slack(self, displacement: f32) -> bool {
+        match self {
+            ConstraintKind::Rope => displacement <= 0.0,
+            ConstraintKind::Strut => displacement >= 0.0,
+            ConstraintKind::Spring | ConstraintKind::Rod => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Constraint {
     point1: u64,
     point2: u64,
     distance: f32,
+    kind: ConstraintKind,
+    stiffness: Option<f32>,
+    damping: Option<f32>,
+    break_threshold: Option<BreakThreshold>,
+    is_colliding: bool,
 }
 impl Constraint {
-    pub fn new(point1: u64, point2: u64, distance: f32) -> Self {
+    pub fn new(
+        point1: u64,
+        point2: u64,
+        distance: f32,
+        kind: ConstraintKind,
+        stiffness: Option<f32>,
+        damping: Option<f32>,
+        break_threshold: Option<BreakThreshold>,
+    ) -> Self {
         Self {
             point1,
             point2,
             distance,
+            kind,
+            stiffness,
+            damping,
+            break_threshold,
+            is_colliding: false,
         }
     }
 
@@ -24,4 +84,39 @@ impl Constraint {
     pub fn get_distance(&self) -> f32 {
         self.distance
     }
+
+    pub fn get_kind(&self) -> ConstraintKind {
+        self.kind
+    }
+
+    pub fn get_stiffness(&self) -> Option<f32> {
+        self.stiffness
+    }
+
+    pub fn get_damping(&self) -> Option<f32> {
+        self.damping
+    }
+
+    /// Stiffness of the constraint, falling back to `default` when it has none of its own
+    pub fn get_stiffness_or(&self, default: f32) -> f32 {
+        self.stiffness.unwrap_or(default)
+    }
+
+    /// Damping of the constraint, falling back to `default` when it has none of its own
+    pub fn get_damping_or(&self, default: f32) -> f32 {
+        self.damping.unwrap_or(default)
+    }
+
+    pub fn get_break_threshold(&self) -> Option<BreakThreshold> {
+        self.break_threshold
+    }
+
+    /// Whether other points collide with the segment between the two points
+    pub fn is_colliding(&self) -> bool {
+        self.is_colliding
+    }
+
+    pub fn set_colliding(&mut self, is_colliding: bool) {
+        self.is_colliding = is_colliding;
+    }
 }
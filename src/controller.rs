@@ -1,56 +1,205 @@
-use macroquad::{math::vec2, miniquad::window::screen_size};
+use std::collections::{HashMap, HashSet};
+
+use macroquad::{
+    math::{vec2, Vec2},
+    miniquad::window::{clipboard_get, clipboard_set, screen_size},
+};
 
 use crate::{
-    constraint::Constraint, input::Operation, physics_system::PhysicsSystem, point::Point,
-    renderer::Renderer, simulator::Simulator, ui_renderer::UiRenderer,
+    camera::Camera,
+    constraint::{BreakThreshold, Constraint, ConstraintKind},
+    force_field::{Falloff, ForceField, ForceFieldKind},
+    history::{EditCommand, History},
+    input::Operation,
+    math::closest_point_on_segment,
+    obstacle::{Obstacle, ObstacleShape},
+    physics_system::PhysicsSystem,
+    point::{CollisionFilter, Point},
+    prefab::{Prefab, PrefabParams},
+    renderer::{Highlights, Interpolation, Renderer},
+    scene::Scene,
+    simulator::{SimulationEvent, Simulator},
+    ui_renderer::{DebugInfo, PlacementInfo, UiRenderer},
 };
 
-struct SimulationSpeed {
-    speeds: Vec<f32>,
+const SCENE_PATH: &str = "scene.toml";
+/// Middle clicks that move less than this many pixels remove a point instead of panning
+const PAN_CLICK_DISTANCE: f32 = 4.0;
+/// Distance in pixels from a constraint line within which the cursor hovers it
+const CONSTRAINT_PICK_DISTANCE: f32 = 6.0;
+/// Distance in pixels from a force field center within which it is picked
+const FORCE_FIELD_PICK_DISTANCE: f32 = 10.0;
+/// World distance between the heights of terrain drawn with the terrain tool
+const TERRAIN_SAMPLE_SPACING: f32 = 0.01;
+
+struct ValueSelector {
+    values: Vec<f32>,
     current: usize,
 }
-impl SimulationSpeed {
-    fn new() -> Self {
-        Self {
-            speeds: vec![0.1, 0.25, 0.5, 0.75, 1.0, 1.25],
-            current: 4,
-        }
+impl ValueSelector {
+    fn new(values: Vec<f32>, current: usize) -> Self {
+        Self { values, current }
     }
 
-    fn get_speed(&self) -> f32 {
-        self.speeds[self.current]
+    fn get_value(&self) -> f32 {
+        self.values[self.current]
     }
 
-    fn increase_speed(&mut self) {
-        if self.current + 1 < self.speeds.len() {
+    fn increase(&mut self) {
+        if self.current + 1 < self.values.len() {
             self.current += 1;
         }
     }
 
-    fn decrease_speed(&mut self) {
+    fn decrease(&mut self) {
         if self.current > 0 {
             self.current -= 1;
         }
     }
 }
 
+pub struct EditorParams {
+    pub history_depth: usize,
+    pub obstacle_restitution: f32,
+    pub obstacle_friction: f32,
+    /// Peak acceleration of placed attractors, repulsors and vortices
+    pub force_field_strength: f32,
+    pub wind_strength: f32,
+    pub force_field_falloff: Falloff,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObstacleTool {
+    /// Dragged from one end to the other
+    Segment,
+    /// Vertices clicked one by one, closed with `Operation::FinishShape`
+    Polygon,
+    /// Surface points clicked one by one, solid below them
+    Terrain,
+}
+
+/// Force fields are dragged from their center to their edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForceFieldTool {
+    Attractor,
+    Repulsor,
+    /// Blows in the direction of the drag
+    Wind,
+    Vortex,
+}
+
+/// What a left click places
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Point,
+    Prefab(Prefab),
+    Obstacle(ObstacleTool),
+    ForceField(ForceFieldTool),
+}
+impl Tool {
+    pub fn next(self) -> Self {
+        match self {
+            Tool::Point => Tool::Prefab(Prefab::Rope),
+            Tool::Prefab(prefab) => prefab
+                .next()
+                .map_or(Tool::Obstacle(ObstacleTool::Segment), Tool::Prefab),
+            Tool::Obstacle(ObstacleTool::Segment) => Tool::Obstacle(ObstacleTool::Polygon),
+            Tool::Obstacle(ObstacleTool::Polygon) => Tool::Obstacle(ObstacleTool::Terrain),
+            Tool::Obstacle(ObstacleTool::Terrain) => Tool::ForceField(ForceFieldTool::Attractor),
+            Tool::ForceField(ForceFieldTool::Attractor) => {
+                Tool::ForceField(ForceFieldTool::Repulsor)
+            }
+            Tool::ForceField(ForceFieldTool::Repulsor) => Tool::ForceField(ForceFieldTool::Wind),
+            Tool::ForceField(ForceFieldTool::Wind) => Tool::ForceField(ForceFieldTool::Vortex),
+            Tool::ForceField(ForceFieldTool::Vortex) => Tool::Point,
+        }
+    }
+}
+
+pub struct TimeStepParams {
+    pub time_step: f32,
+    pub max_steps_per_frame: usize,
+}
+
+struct BrokenConstraintFlash {
+    from: Vec2,
+    to: Vec2,
+    time_left: f32,
+}
+
 struct ControllerState {
     mouse_pos: (f32, f32),
+    mouse_screen_pos: Vec2,
+    pan_start: Option<Vec2>,
     is_paused: bool,
     is_debug_mode: bool,
-    selected_point: Option<u64>,
-    is_draging: bool,
-    simualtion_speed: SimulationSpeed,
+    selection: HashSet<u64>,
+    selected_constraint: Option<(u64, u64)>,
+    constraint_start: Option<u64>,
+    marquee_start: Option<Vec2>,
+    drag_anchor: Option<u64>,
+    drag_start_locations: HashMap<u64, Vec2>,
+    /// Index and starting center of the force field being dragged
+    dragged_force_field: Option<(usize, Vec2)>,
+    simualtion_speed: ValueSelector,
+    point_mass: ValueSelector,
+    point_charge: ValueSelector,
+    point_radius_scale: ValueSelector,
+    constraint_stiffness_scale: ValueSelector,
+    constraint_break_strain: ValueSelector,
+    constraint_break_force: ValueSelector,
+    is_breaking_on_force: bool,
+    constraint_kind: ConstraintKind,
+    accumulator: f32,
+    broken_constraints_count: usize,
+    broken_constraint_flashes: Vec<BrokenConstraintFlash>,
+    clipboard: Option<String>,
+    tool: Tool,
+    prefab_segments: ValueSelector,
+    is_prefab_pinned: bool,
+    force_field_strength_scale: ValueSelector,
+    is_edge_colliding: bool,
+    gesture_start: Option<Vec2>,
+    obstacle_vertices: Vec<Vec2>,
 }
 impl Default for ControllerState {
     fn default() -> Self {
         Self {
             mouse_pos: (0.0, 0.0),
+            mouse_screen_pos: vec2(0.0, 0.0),
+            pan_start: None,
             is_paused: true,
-            selected_point: None,
+            selection: HashSet::new(),
+            selected_constraint: None,
+            constraint_start: None,
+            marquee_start: None,
             is_debug_mode: false,
-            is_draging: false,
-            simualtion_speed: SimulationSpeed::new(),
+            drag_anchor: None,
+            drag_start_locations: HashMap::new(),
+            dragged_force_field: None,
+            simualtion_speed: ValueSelector::new(vec![0.1, 0.25, 0.5, 0.75, 1.0, 1.25], 4),
+            point_mass: ValueSelector::new(vec![0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 25.0], 2),
+            point_charge: ValueSelector::new(vec![-2.0, -1.0, -0.5, 0.0, 0.5, 1.0, 2.0], 3),
+            point_radius_scale: ValueSelector::new(vec![0.5, 0.75, 1.0, 1.5, 2.0, 3.0], 2),
+            constraint_stiffness_scale: ValueSelector::new(
+                vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 4.0],
+                4,
+            ),
+            constraint_break_strain: ValueSelector::new(vec![0.0, 0.05, 0.1, 0.25, 0.5, 1.0], 0),
+            constraint_break_force: ValueSelector::new(vec![0.0, 1.0, 2.0, 5.0, 10.0, 20.0], 0),
+            is_breaking_on_force: false,
+            constraint_kind: ConstraintKind::Spring,
+            accumulator: 0.0,
+            broken_constraints_count: 0,
+            broken_constraint_flashes: vec![],
+            clipboard: None,
+            tool: Tool::Point,
+            prefab_segments: ValueSelector::new(vec![2.0, 4.0, 6.0, 8.0, 12.0, 16.0, 24.0], 3),
+            is_prefab_pinned: false,
+            force_field_strength_scale: ValueSelector::new(vec![0.25, 0.5, 1.0, 2.0, 4.0], 2),
+            is_edge_colliding: false,
+            gesture_start: None,
+            obstacle_vertices: vec![],
         }
     }
 }
@@ -60,6 +209,11 @@ pub struct Controller {
     simulator: Simulator,
     renderer: Renderer,
     ui_renderer: UiRenderer,
+    camera: Camera,
+    time_step_params: TimeStepParams,
+    editor_params: EditorParams,
+    previous_locations: HashMap<u64, Vec2>,
+    history: History,
     state: ControllerState,
 }
 impl Controller {
@@ -68,12 +222,19 @@ impl Controller {
         simulator: Simulator,
         renderer: Renderer,
         ui_renderer: UiRenderer,
+        time_step_params: TimeStepParams,
+        editor_params: EditorParams,
     ) -> Self {
         Self {
+            camera: Camera::fit_bounding_box(simulator.get_bounding_box()),
             physics_system,
             simulator,
             renderer,
             ui_renderer,
+            time_step_params,
+            previous_locations: HashMap::new(),
+            history: History::new(editor_params.history_depth),
+            editor_params,
             state: ControllerState::default(),
         }
     }
@@ -87,71 +248,183 @@ impl Controller {
         self.state.mouse_pos.0 = *x;
         self.state.mouse_pos.1 = *y;
 
-        if !self.state.is_draging {
-            return;
-        }
-        if self.state.selected_point.is_none() {
-            self.state.is_draging = false;
-            return;
+        if let Some((index, _)) = self.state.dragged_force_field {
+            if let Some(force_field) = self.physics_system.get_force_field_mut(index) {
+                force_field.center = vec2(*x, *y);
+            }
         }
-        let id = self.state.selected_point.expect("Id should be valid");
 
-        let option_point = self.physics_system.get_point_mut(id);
-        if option_point.is_none() {
-            self.state.is_draging = false;
-            self.state.selected_point = None;
+        let Some(anchor_id) = self.state.drag_anchor else {
             return;
-        }
-        let point = option_point.unwrap();
+        };
+        let Some(anchor) = self.physics_system.get_point(anchor_id) else {
+            self.state.drag_anchor = None;
+            self.state.drag_start_locations.clear();
+            return;
+        };
 
-        point.location = vec2(self.state.mouse_pos.0, self.state.mouse_pos.1);
-        point.velocity = Simulator::calculate_velocity(
-            vec2(old_mouse_pos.0, old_mouse_pos.1),
-            point.location,
-            delta,
-        );
+        let mouse_pos = vec2(self.state.mouse_pos.0, self.state.mouse_pos.1);
+        let offset = mouse_pos - anchor.location;
+        let velocity =
+            Simulator::calculate_velocity(vec2(old_mouse_pos.0, old_mouse_pos.1), mouse_pos, delta);
+        for id in self.state.drag_start_locations.keys() {
+            if let Some(point) = self.physics_system.get_point_mut(*id) {
+                point.location += offset;
+                point.velocity = velocity;
+            }
+        }
     }
 
     fn find_point_id_for_location(&self, x: f32, y: f32) -> Option<u64> {
-        let point_size = self.renderer.get_draw_params().point_size;
         self.physics_system
             .get_points_ids()
             .iter()
-            .find(|(_id, point)| point.location.distance(vec2(x, y)) <= point_size)
+            .find(|(_id, point)| point.location.distance(vec2(x, y)) <= point.radius)
             .map(|(id, _point)| *id)
     }
 
+    /// Closest constraint within `CONSTRAINT_PICK_DISTANCE` of the location, as its point ids
+    fn find_constraint_for_location(&self, location: Vec2) -> Option<(u64, u64)> {
+        let pick_distance = CONSTRAINT_PICK_DISTANCE / self.camera.get_scale(screen_size());
+
+        self.physics_system
+            .get_constraints()
+            .iter()
+            .filter_map(|constraint| {
+                let from = self.physics_system.get_point(constraint.get_point1())?;
+                let to = self.physics_system.get_point(constraint.get_point2())?;
+                let distance = location.distance(closest_point_on_segment(
+                    location,
+                    from.location,
+                    to.location,
+                ));
+                (distance <= pick_distance).then_some((constraint, distance))
+            })
+            .min_by(|(_, distance1), (_, distance2)| distance1.total_cmp(distance2))
+            .map(|(constraint, _)| (constraint.get_point1(), constraint.get_point2()))
+    }
+
+    /// Points take precedence, so a constraint is only hovered away from its ends
+    fn get_hovered_constraint(&self) -> Option<(u64, u64)> {
+        let (x, y) = self.state.mouse_pos;
+        if self.find_point_id_for_location(x, y).is_some() {
+            return None;
+        }
+
+        self.find_constraint_for_location(vec2(x, y))
+    }
+
+    fn get_placement_radius(&self) -> f32 {
+        self.simulator.get_params().point_size * self.state.point_radius_scale.get_value()
+    }
+
+    /// `None` when the global spring coefficient should be used
+    fn get_placement_stiffness(&self) -> Option<f32> {
+        let scale = self.state.constraint_stiffness_scale.get_value();
+        if scale == 1.0 {
+            None
+        } else {
+            Some(self.simulator.get_params().spring_coeff * scale)
+        }
+    }
+
+    fn get_active_break_selector(&mut self) -> &mut ValueSelector {
+        if self.state.is_breaking_on_force {
+            &mut self.state.constraint_break_force
+        } else {
+            &mut self.state.constraint_break_strain
+        }
+    }
+
+    /// `None` when new constraints should be unbreakable
+    /// Rods never get a force threshold, as they carry no spring force
+    fn get_placement_break_threshold(&self) -> Option<BreakThreshold> {
+        if self.state.is_breaking_on_force {
+            if self.state.constraint_kind == ConstraintKind::Rod {
+                return None;
+            }
+
+            let force = self.state.constraint_break_force.get_value();
+            (force > 0.0).then_some(BreakThreshold::Force(force))
+        } else {
+            let strain = self.state.constraint_break_strain.get_value();
+            (strain > 0.0).then_some(BreakThreshold::Strain(strain))
+        }
+    }
+
     fn handle_mouse_down(&mut self, x: &f32, y: &f32) {
-        self.state.is_draging = false;
-        let point_id = self.find_point_id_for_location(*x, *y);
-        if point_id.is_some() {
-            self.state.selected_point = point_id;
+        match self.state.tool {
+            Tool::Point => {}
+            Tool::Prefab(_) | Tool::Obstacle(ObstacleTool::Segment) | Tool::ForceField(_) => {
+                self.state.gesture_start = Some(vec2(*x, *y));
+                return;
+            }
+            Tool::Obstacle(ObstacleTool::Polygon | ObstacleTool::Terrain) => {
+                self.state.obstacle_vertices.push(vec2(*x, *y));
+                return;
+            }
+        }
+
+        self.state.constraint_start = self.find_point_id_for_location(*x, *y);
+        if self.state.constraint_start.is_some() {
             return;
         }
 
-        self.physics_system
-            .add_point(Point::new(vec2(*x, *y), vec2(0.0, 0.0), false));
+        self.state.selected_constraint = self.find_constraint_for_location(vec2(*x, *y));
+        if self.state.selected_constraint.is_some() {
+            return;
+        }
+
+        let mut point = Point::new(
+            vec2(*x, *y),
+            vec2(0.0, 0.0),
+            false,
+            self.get_placement_radius(),
+            self.state.point_mass.get_value(),
+        );
+        point.charge = self.state.point_charge.get_value();
+        let id = self.physics_system.add_point(point.clone());
+        self.history.record(EditCommand::AddPoint { id, point });
     }
 
     fn toggle_static(&mut self, id: u64) {
-        let option_point = self.physics_system.get_point_mut(id);
-        if option_point.is_some() {
-            let point = option_point.unwrap();
+        if let Some(point) = self.physics_system.get_point_mut(id) {
             point.is_static = !point.is_static;
+            self.history.record(EditCommand::ToggleStatic { id });
         }
     }
 
     fn handle_mouse_up(&mut self, x: &f32, y: &f32) {
-        self.state.is_draging = false;
+        if let Some(marquee_start) = self.state.marquee_start.take() {
+            self.select_in_rectangle(marquee_start, vec2(*x, *y));
+            return;
+        }
+
+        if let Some(gesture_start) = self.state.gesture_start.take() {
+            match self.state.tool {
+                Tool::Prefab(_) => self.place_prefab(gesture_start, vec2(*x, *y)),
+                Tool::Obstacle(ObstacleTool::Segment)
+                    if gesture_start.distance(vec2(*x, *y)) > self.get_placement_radius() =>
+                {
+                    self.add_obstacle(ObstacleShape::Segment {
+                        from: gesture_start,
+                        to: vec2(*x, *y),
+                    })
+                }
+                Tool::ForceField(_) => self.place_force_field(gesture_start, vec2(*x, *y)),
+                _ => {}
+            }
+            return;
+        }
+
         let point_id = self.find_point_id_for_location(*x, *y);
-        if point_id.is_none() || self.state.selected_point.is_none() {
-            self.state.selected_point = None;
+        let constraint_start = self.state.constraint_start.take();
+        if point_id.is_none() || constraint_start.is_none() {
             return;
         }
 
         let id1 = point_id.unwrap();
-        let id2 = self.state.selected_point.unwrap();
-        self.state.selected_point = None;
+        let id2 = constraint_start.unwrap();
 
         if id1 == id2 {
             self.toggle_static(id1);
@@ -166,85 +439,919 @@ impl Controller {
         }
 
         let distance = point1.unwrap().location.distance(point2.unwrap().location);
-        let constraint = Constraint::new(id1, id2, distance);
+        let mut constraint = Constraint::new(
+            id1,
+            id2,
+            distance,
+            self.state.constraint_kind,
+            self.get_placement_stiffness(),
+            None,
+            self.get_placement_break_threshold(),
+        );
+        constraint.set_colliding(self.state.is_edge_colliding);
 
-        self.physics_system.add_constraint(constraint);
+        if self.physics_system.add_constraint(constraint.clone()) {
+            self.history
+                .record(EditCommand::AddConstraint { constraint });
+        }
     }
 
-    fn handle_right_click(&mut self, x: &f32, y: &f32) {
-        self.state.is_draging = false;
-        self.state.selected_point = None;
+    fn handle_remove(&mut self, x: &f32, y: &f32) {
+        self.reset_interaction();
         let id = self.find_point_id_for_location(*x, *y);
         if id.is_none() {
+            if let Some(index) = self.find_force_field_for_location(vec2(*x, *y)) {
+                self.remove_force_field(index);
+            } else {
+                self.remove_obstacle_at(vec2(*x, *y));
+            }
+            return;
+        }
+
+        let id = id.unwrap();
+        self.state.selection.remove(&id);
+        if let Some((point, constraints)) = self.physics_system.remove_point(id) {
+            self.history.record(EditCommand::RemovePoint {
+                id,
+                point,
+                constraints,
+            });
+        }
+    }
+
+    fn remove_obstacle_at(&mut self, location: Vec2) {
+        let Some(index) = self.find_obstacle_for_location(location) else {
+            return;
+        };
+
+        if let Some(obstacle) = self.physics_system.remove_obstacle(index) {
+            self.history
+                .record(EditCommand::RemoveObstacle { index, obstacle });
+        }
+    }
+
+    fn remove_force_field(&mut self, index: usize) {
+        if let Some(force_field) = self.physics_system.remove_force_field(index) {
+            self.history
+                .record(EditCommand::RemoveForceField { index, force_field });
+        }
+    }
+
+    fn screen_to_world(&self, x: f32, y: f32) -> Vec2 {
+        self.camera.screen_to_world(vec2(x, y), screen_size())
+    }
+
+    fn handle_mouse_position(&mut self, x: f32, y: f32, delta: f32) {
+        let mouse_screen_pos = vec2(x, y);
+        if self.state.pan_start.is_some() {
+            self.camera.pan(
+                mouse_screen_pos - self.state.mouse_screen_pos,
+                screen_size(),
+            );
+        }
+        self.state.mouse_screen_pos = mouse_screen_pos;
+
+        let location = self.screen_to_world(x, y);
+        self.handle_move(&location.x, &location.y, delta);
+    }
+
+    fn handle_pan_start(&mut self, x: f32, y: f32) {
+        self.state.mouse_screen_pos = vec2(x, y);
+        self.state.pan_start = Some(vec2(x, y));
+    }
+
+    fn handle_pan_end(&mut self, x: f32, y: f32) {
+        let Some(pan_start) = self.state.pan_start.take() else {
+            return;
+        };
+        if pan_start.distance(vec2(x, y)) < PAN_CLICK_DISTANCE {
+            let location = self.screen_to_world(x, y);
+            self.handle_remove(&location.x, &location.y);
+        }
+    }
+
+    fn handle_pan(&mut self, dx: f32, dy: f32) {
+        self.camera.pan(vec2(dx, dy), screen_size());
+    }
+
+    fn handle_zoom(&mut self, factor: f32, x: f32, y: f32) {
+        self.camera.zoom_at(factor, vec2(x, y), screen_size());
+    }
+
+    fn handle_reset_camera(&mut self) {
+        self.camera = Camera::fit_bounding_box(self.simulator.get_bounding_box());
+    }
+
+    /// Shift click toggles a point in the selection, on empty space it starts a marquee
+    fn handle_select_start(&mut self, x: &f32, y: &f32) {
+        self.state.constraint_start = None;
+        match self.find_point_id_for_location(*x, *y) {
+            Some(id) => {
+                if !self.state.selection.remove(&id) {
+                    self.state.selection.insert(id);
+                }
+            }
+            None => self.state.marquee_start = Some(vec2(*x, *y)),
+        }
+    }
+
+    fn select_in_rectangle(&mut self, corner1: Vec2, corner2: Vec2) {
+        let min = corner1.min(corner2);
+        let max = corner1.max(corner2);
+        let selected = self
+            .physics_system
+            .get_points_ids()
+            .iter()
+            .filter(|(_id, point)| {
+                point.location.cmpge(min).all() && point.location.cmple(max).all()
+            })
+            .map(|(id, _point)| *id);
+
+        self.state.selection.extend(selected);
+    }
+
+    fn handle_clear_selection(&mut self) {
+        self.state.obstacle_vertices.clear();
+        self.state.selection.clear();
+        self.state.selected_constraint = None;
+    }
+
+    fn handle_delete(&mut self) {
+        let mut commands = vec![];
+        if let Some((point1, point2)) = self.state.selected_constraint.take() {
+            if let Some(constraint) = self.physics_system.remove_constraint(point1, point2) {
+                commands.push(EditCommand::RemoveConstraint { constraint });
+            }
+        }
+
+        for id in std::mem::take(&mut self.state.selection) {
+            if let Some((point, constraints)) = self.physics_system.remove_point(id) {
+                commands.push(EditCommand::RemovePoint {
+                    id,
+                    point,
+                    constraints,
+                });
+            }
+        }
+
+        self.reset_interaction();
+        self.history.record_batch(commands);
+    }
+
+    fn handle_toggle_static_selection(&mut self) {
+        let mut commands = vec![];
+        for id in &self.state.selection {
+            if let Some(point) = self.physics_system.get_point_mut(*id) {
+                point.is_static = !point.is_static;
+                commands.push(EditCommand::ToggleStatic { id: *id });
+            }
+        }
+
+        self.history.record_batch(commands);
+    }
+
+    /// Sets the collision filter of every selected point with `update`, as one undo step
+    fn update_selection_collision_filter(&mut self, update: impl Fn(&mut CollisionFilter)) {
+        let mut commands = vec![];
+        for id in &self.state.selection {
+            if let Some(point) = self.physics_system.get_point_mut(*id) {
+                let from = point.collision_filter;
+                update(&mut point.collision_filter);
+                let to = point.collision_filter;
+                if from != to {
+                    commands.push(EditCommand::SetCollisionFilter { id: *id, from, to });
+                }
+            }
+        }
+
+        self.history.record_batch(commands);
+    }
+
+    fn get_used_collision_groups(&self) -> HashSet<u32> {
+        self.physics_system
+            .get_points_ids()
+            .values()
+            .map(|point| point.collision_filter.group)
+            .collect()
+    }
+
+    /// Lowest collision group missing from `used`, marked as used, or none if all are taken
+    fn take_unused_collision_group(used: &mut HashSet<u32>) -> Option<u32> {
+        let group = (1..=u32::MAX).find(|group| !used.contains(group))?;
+        used.insert(group);
+
+        Some(group)
+    }
+
+    /// Puts the selected points in a new collision group so they stop colliding with each
+    /// other, or takes them out of it if they already share one
+    fn handle_group_selection(&mut self) {
+        let groups: HashSet<_> = self
+            .state
+            .selection
+            .iter()
+            .filter_map(|id| self.physics_system.get_point(*id))
+            .map(|point| point.collision_filter.group)
+            .collect();
+        let group = match groups.iter().next() {
+            Some(group) if groups.len() == 1 && *group != 0 => 0,
+            _ => match Self::take_unused_collision_group(&mut self.get_used_collision_groups()) {
+                Some(group) => group,
+                // Only possible with more points than there are groups
+                None => return,
+            },
+        };
+
+        self.update_selection_collision_filter(|filter| filter.group = group);
+    }
+
+    /// Stops the selected points from colliding with anything, or makes them collide with
+    /// everything again if none of them collides
+    fn handle_toggle_colliding_selection(&mut self) {
+        let is_any_colliding = self
+            .state
+            .selection
+            .iter()
+            .filter_map(|id| self.physics_system.get_point(*id))
+            .any(|point| point.collision_filter.mask != 0);
+        let mask = if is_any_colliding {
+            0
+        } else {
+            CollisionFilter::default().mask
+        };
+
+        self.update_selection_collision_filter(|filter| filter.mask = mask);
+    }
+
+    fn handle_toggle_skip_connected_collisions(&mut self) {
+        self.simulator.toggle_skip_connected_collisions();
+    }
+
+    /// Toggles edge collision of the selected constraint, or else of every constraint inside
+    /// the selected points, or else of newly placed constraints
+    fn handle_toggle_edge_collision(&mut self) {
+        let pairs: Vec<_> = if let Some(pair) = self.state.selected_constraint {
+            vec![pair]
+        } else {
+            self.physics_system
+                .get_constraints()
+                .iter()
+                .filter(|c| {
+                    self.state.selection.contains(&c.get_point1())
+                        && self.state.selection.contains(&c.get_point2())
+                })
+                .map(|c| (c.get_point1(), c.get_point2()))
+                .collect()
+        };
+        if pairs.is_empty() && self.state.selection.is_empty() {
+            self.state.is_edge_colliding = !self.state.is_edge_colliding;
+            return;
+        }
+
+        let mut commands = vec![];
+        for (point1, point2) in pairs {
+            if let Some(constraint) = self.physics_system.find_constraint_mut(point1, point2) {
+                constraint.set_colliding(!constraint.is_colliding());
+                commands.push(EditCommand::ToggleEdgeCollision { point1, point2 });
+            }
+        }
+
+        self.history.record_batch(commands);
+    }
+
+    fn handle_zero_velocity_selection(&mut self) {
+        let mut commands = vec![];
+        for id in &self.state.selection {
+            if let Some(point) = self.physics_system.get_point_mut(*id) {
+                let from = point.velocity;
+                point.velocity = vec2(0.0, 0.0);
+                commands.push(EditCommand::SetVelocity {
+                    id: *id,
+                    from,
+                    to: point.velocity,
+                });
+            }
+        }
+
+        self.history.record_batch(commands);
+    }
+
+    /// Copies the selection as scene text, both to the system clipboard and an internal one
+    fn handle_copy(&mut self) {
+        if self.state.selection.is_empty() {
+            return;
+        }
+
+        let copied = self.physics_system.copy_subset(&self.state.selection);
+        match Scene::new(&copied, self.simulator.get_params()).to_toml() {
+            Ok(text) => {
+                clipboard_set(&text);
+                self.state.clipboard = Some(text);
+            }
+            Err(err) => println!("Error copying selection: {err}"),
+        }
+    }
+
+    /// Pastes from the system clipboard, falling back to the internal one if it has no scene
+    fn handle_paste(&mut self) {
+        let scene = clipboard_get()
+            .into_iter()
+            .chain(self.state.clipboard.clone())
+            .find_map(|text| Scene::from_toml(&text).ok());
+
+        if let Some(scene) = scene {
+            let (pasted, _params) = scene.into_parts();
+            self.paste(&pasted);
+        }
+    }
+
+    fn get_prefab_params(&self) -> PrefabParams {
+        PrefabParams {
+            segments: self.state.prefab_segments.get_value() as usize,
+            is_pinned: self.state.is_prefab_pinned,
+            radius: self.get_placement_radius(),
+            mass: self.state.point_mass.get_value(),
+            charge: self.state.point_charge.get_value(),
+            kind: self.state.constraint_kind,
+            stiffness: self.get_placement_stiffness(),
+            break_threshold: self.get_placement_break_threshold(),
+            is_colliding: self.state.is_edge_colliding,
+        }
+    }
+
+    fn build_prefab(&self, from: Vec2, to: Vec2) -> Option<PhysicsSystem> {
+        let Tool::Prefab(prefab) = self.state.tool else {
+            return None;
+        };
+
+        Some(prefab.build(from, to, &self.get_prefab_params()))
+    }
+
+    fn place_prefab(&mut self, from: Vec2, to: Vec2) {
+        if let Some(system) = self.build_prefab(from, to) {
+            self.insert_system(&system, vec2(0.0, 0.0));
+        }
+    }
+
+    fn handle_cycle_tool(&mut self) {
+        self.reset_interaction();
+        self.state.tool = self.state.tool.next();
+    }
+
+    fn add_obstacle(&mut self, shape: ObstacleShape) {
+        let obstacle = Obstacle::new(
+            shape,
+            self.editor_params.obstacle_restitution,
+            self.editor_params.obstacle_friction,
+        );
+        let index = self.physics_system.add_obstacle(obstacle.clone());
+        self.history
+            .record(EditCommand::AddObstacle { index, obstacle });
+    }
+
+    /// Closes the polygon or terrain drawn so far, dropping it if it has too few vertices
+    fn handle_finish_shape(&mut self) {
+        let vertices = std::mem::take(&mut self.state.obstacle_vertices);
+        let shape = match self.state.tool {
+            Tool::Obstacle(ObstacleTool::Polygon) if vertices.len() >= 3 => {
+                Some(ObstacleShape::Polygon { vertices })
+            }
+            Tool::Obstacle(ObstacleTool::Terrain) => {
+                ObstacleShape::heightmap_from_vertices(&vertices, TERRAIN_SAMPLE_SPACING)
+            }
+            _ => None,
+        };
+
+        if let Some(shape) = shape {
+            self.add_obstacle(shape);
+        }
+    }
+
+    /// Obstacle containing the location or with an outline within `CONSTRAINT_PICK_DISTANCE`
+    fn find_obstacle_for_location(&self, location: Vec2) -> Option<usize> {
+        let pick_distance = CONSTRAINT_PICK_DISTANCE / self.camera.get_scale(screen_size());
+
+        self.physics_system
+            .get_obstacles()
+            .iter()
+            .position(|obstacle| {
+                obstacle.shape.contains(location)
+                    || obstacle
+                        .get_closest_point(location)
+                        .is_some_and(|(_, distance)| distance <= pick_distance)
+            })
+    }
+
+    /// Force field of the current tool from its center to a point on its edge, `None` for
+    /// other tools or a gesture shorter than a point
+    fn build_force_field(&self, center: Vec2, edge: Vec2) -> Option<ForceField> {
+        let Tool::ForceField(tool) = self.state.tool else {
+            return None;
+        };
+        let radius = center.distance(edge);
+        if radius <= self.get_placement_radius() {
+            return None;
+        }
+
+        let scale = self.state.force_field_strength_scale.get_value();
+        let strength = self.editor_params.force_field_strength * scale;
+        let radial = ForceFieldKind::Radial {
+            falloff: self.editor_params.force_field_falloff,
+        };
+        let (kind, strength) = match tool {
+            ForceFieldTool::Attractor => (radial, strength),
+            ForceFieldTool::Repulsor => (radial, -strength),
+            ForceFieldTool::Wind => (
+                ForceFieldKind::Wind {
+                    direction: (edge - center) / radius,
+                },
+                self.editor_params.wind_strength * scale,
+            ),
+            ForceFieldTool::Vortex => (ForceFieldKind::Vortex, strength),
+        };
+
+        Some(ForceField::new(kind, center, radius, strength))
+    }
+
+    fn place_force_field(&mut self, center: Vec2, edge: Vec2) {
+        let Some(force_field) = self.build_force_field(center, edge) else {
             return;
+        };
+
+        let index = self.physics_system.add_force_field(force_field.clone());
+        self.history
+            .record(EditCommand::AddForceField { index, force_field });
+    }
+
+    fn find_force_field_for_location(&self, location: Vec2) -> Option<usize> {
+        let pick_distance = FORCE_FIELD_PICK_DISTANCE / self.camera.get_scale(screen_size());
+
+        self.physics_system
+            .get_force_fields()
+            .iter()
+            .position(|force_field| force_field.center.distance(location) <= pick_distance)
+    }
+
+    /// Changes the strength of placed force fields with a force field tool, or else the prefab
+    /// segments
+    fn handle_increase_tool_setting(&mut self) {
+        match self.state.tool {
+            Tool::ForceField(_) => self.state.force_field_strength_scale.increase(),
+            _ => self.state.prefab_segments.increase(),
+        }
+    }
+
+    fn handle_decrease_tool_setting(&mut self) {
+        match self.state.tool {
+            Tool::ForceField(_) => self.state.force_field_strength_scale.decrease(),
+            _ => self.state.prefab_segments.decrease(),
+        }
+    }
+
+    fn handle_toggle_prefab_pinned(&mut self) {
+        self.state.is_prefab_pinned = !self.state.is_prefab_pinned;
+    }
+
+    fn handle_duplicate(&mut self) {
+        let copied = self.physics_system.copy_subset(&self.state.selection);
+        self.paste(&copied);
+    }
+
+    /// Pastes the points of `pasted` centered on the cursor
+    fn paste(&mut self, pasted: &PhysicsSystem) {
+        let points = pasted.get_points_ids();
+        if points.is_empty() {
+            return;
+        }
+
+        let center = points
+            .values()
+            .fold(vec2(0.0, 0.0), |sum, point| sum + point.location)
+            / points.len() as f32;
+        let offset = vec2(self.state.mouse_pos.0, self.state.mouse_pos.1) - center;
+
+        self.insert_system(pasted, offset);
+    }
+
+    /// Adds the points of `inserted` under new ids, moved by `offset`, and selects them
+    fn insert_system(&mut self, inserted: &PhysicsSystem, offset: Vec2) {
+        let points = inserted.get_points_ids();
+        if points.is_empty() {
+            return;
+        }
+
+        let old_ids = inserted.get_sorted_ids();
+
+        let mut commands = vec![];
+        let mut new_ids = HashMap::with_capacity(old_ids.len());
+        // Copies get groups of their own while any are free, so they still collide with the
+        // originals
+        let mut new_groups = HashMap::new();
+        let mut used_groups = self.get_used_collision_groups();
+        for &old_id in old_ids {
+            let mut point = points[&old_id].clone();
+            let group = point.collision_filter.group;
+            if group != 0 && !new_groups.contains_key(&group) {
+                if let Some(new_group) = Self::take_unused_collision_group(&mut used_groups) {
+                    new_groups.insert(group, new_group);
+                }
+            }
+            if let Some(new_group) = new_groups.get(&group) {
+                point.collision_filter.group = *new_group;
+            }
+            point.location += offset;
+            point.velocity = vec2(0.0, 0.0);
+            let id = self.physics_system.add_point(point.clone());
+            new_ids.insert(old_id, id);
+            commands.push(EditCommand::AddPoint { id, point });
+        }
+
+        for c in inserted.get_constraints() {
+            let mut constraint = Constraint::new(
+                new_ids[&c.get_point1()],
+                new_ids[&c.get_point2()],
+                c.get_distance(),
+                c.get_kind(),
+                c.get_stiffness(),
+                c.get_damping(),
+                c.get_break_threshold(),
+            );
+            constraint.set_colliding(c.is_colliding());
+            if self.physics_system.add_constraint(constraint.clone()) {
+                commands.push(EditCommand::AddConstraint { constraint });
+            }
         }
 
-        self.physics_system.remove_point(id.unwrap());
+        self.reset_interaction();
+        self.state.selected_constraint = None;
+        self.state.selection = new_ids.into_values().collect();
+        self.history.record_batch(commands);
+    }
+
+    /// Drops ids of points that no longer exist, such as after an undo
+    fn retain_existing_selection(&mut self) {
+        let physics_system = &self.physics_system;
+        self.state
+            .selection
+            .retain(|id| physics_system.get_point(*id).is_some());
     }
 
     fn handle_toggle_debug(&mut self) {
         self.state.is_debug_mode = !self.state.is_debug_mode;
     }
 
+    /// Dragging a selected point moves the whole selection, any other point is moved alone
     fn handle_drag_start(&mut self, x: &f32, y: &f32) {
-        self.state.selected_point = None;
-        let id = self.find_point_id_for_location(*x, *y);
-        if id.is_none() {
+        self.state.constraint_start = None;
+        let Some(id) = self.find_point_id_for_location(*x, *y) else {
+            self.state.dragged_force_field = self
+                .find_force_field_for_location(vec2(*x, *y))
+                .map(|index| (index, self.physics_system.get_force_fields()[index].center));
             return;
+        };
+
+        let moved_ids = if self.state.selection.contains(&id) {
+            self.state.selection.iter().copied().collect()
+        } else {
+            vec![id]
+        };
+        self.state.drag_anchor = Some(id);
+        self.state.drag_start_locations = moved_ids
+            .into_iter()
+            .filter_map(|id| Some((id, self.physics_system.get_point(id)?.location)))
+            .collect();
+    }
+
+    fn record_drag(&mut self) {
+        if let Some((index, from)) = self.state.dragged_force_field {
+            if let Some(force_field) = self.physics_system.get_force_fields().get(index) {
+                if force_field.center != from {
+                    self.history.record(EditCommand::MoveForceField {
+                        index,
+                        from,
+                        to: force_field.center,
+                    });
+                }
+            }
         }
-        self.state.selected_point = id;
-        self.state.is_draging = true;
+
+        let commands = self
+            .state
+            .drag_start_locations
+            .iter()
+            .filter_map(|(id, from)| {
+                let to = self.physics_system.get_point(*id)?.location;
+                (to != *from).then_some(EditCommand::MovePoint {
+                    id: *id,
+                    from: *from,
+                    to,
+                })
+            })
+            .collect();
+
+        self.history.record_batch(commands);
     }
 
     fn handle_drag_end(&mut self) {
-        self.state.is_draging = false;
-        self.state.selected_point = None;
+        self.record_drag();
+        self.reset_interaction();
+    }
+
+    /// Cancels any edit in progress, the selection itself is kept
+    fn reset_interaction(&mut self) {
+        self.state.constraint_start = None;
+        self.state.gesture_start = None;
+        self.state.obstacle_vertices.clear();
+        self.state.marquee_start = None;
+        self.state.drag_anchor = None;
+        self.state.drag_start_locations.clear();
+        self.state.dragged_force_field = None;
+    }
+
+    fn handle_undo(&mut self) {
+        self.reset_interaction();
+        self.history.undo(&mut self.physics_system);
+        self.retain_existing_selection();
+    }
+
+    fn handle_redo(&mut self) {
+        self.reset_interaction();
+        self.history.redo(&mut self.physics_system);
+        self.retain_existing_selection();
     }
 
     fn handle_increase_simulation_speed(&mut self) {
-        self.state.simualtion_speed.increase_speed();
+        self.state.simualtion_speed.increase();
     }
 
     fn handle_decrease_simulation_speed(&mut self) {
-        self.state.simualtion_speed.decrease_speed();
+        self.state.simualtion_speed.decrease();
+    }
+
+    fn handle_increase_point_mass(&mut self) {
+        self.state.point_mass.increase();
+    }
+
+    fn handle_decrease_point_mass(&mut self) {
+        self.state.point_mass.decrease();
+    }
+
+    fn handle_increase_point_charge(&mut self) {
+        self.state.point_charge.increase();
+    }
+
+    fn handle_decrease_point_charge(&mut self) {
+        self.state.point_charge.decrease();
+    }
+
+    fn handle_increase_point_radius(&mut self) {
+        self.state.point_radius_scale.increase();
+    }
+
+    fn handle_decrease_point_radius(&mut self) {
+        self.state.point_radius_scale.decrease();
+    }
+
+    fn handle_increase_constraint_stiffness(&mut self) {
+        self.state.constraint_stiffness_scale.increase();
+    }
+
+    fn handle_decrease_constraint_stiffness(&mut self) {
+        self.state.constraint_stiffness_scale.decrease();
+    }
+
+    fn handle_increase_constraint_break_threshold(&mut self) {
+        self.get_active_break_selector().increase();
+    }
+
+    fn handle_decrease_constraint_break_threshold(&mut self) {
+        self.get_active_break_selector().decrease();
+    }
+
+    fn handle_toggle_break_on_force(&mut self) {
+        self.state.is_breaking_on_force = !self.state.is_breaking_on_force;
+    }
+
+    fn handle_cycle_constraint_kind(&mut self) {
+        self.state.constraint_kind = self.state.constraint_kind.next();
+    }
+
+    fn handle_toggle_integrator(&mut self) {
+        self.simulator.toggle_integrator();
+    }
+
+    fn handle_save_scene(&self) {
+        let scene = Scene::new(&self.physics_system, self.simulator.get_params());
+        match scene.save(SCENE_PATH) {
+            Ok(()) => println!("Saved scene to '{SCENE_PATH}'"),
+            Err(err) => println!("Error saving scene to '{SCENE_PATH}': {err}"),
+        }
+    }
+
+    fn handle_load_scene(&mut self) {
+        match Scene::load(SCENE_PATH) {
+            Ok(scene) => {
+                let (physics_system, params) = scene.into_parts();
+                self.physics_system = physics_system;
+                self.simulator.set_params(params);
+                self.reset_time_step();
+                self.history.clear();
+                self.reset_interaction();
+                self.handle_clear_selection();
+                println!("Loaded scene from '{SCENE_PATH}'");
+            }
+            Err(err) => println!("Error loading scene from '{SCENE_PATH}': {err}"),
+        }
+    }
+
+    fn handle_toggle_broad_phase(&mut self) {
+        self.simulator.toggle_broad_phase();
+    }
+
+    fn handle_toggle_uniform_gravity(&mut self) {
+        self.simulator.toggle_uniform_gravity();
+    }
+
+    fn handle_cycle_n_body_mode(&mut self) {
+        self.simulator.cycle_n_body_mode();
     }
 
     pub fn handle_input(&mut self, input: &[Operation], delta: f32) {
         for operation in input {
             match operation {
                 Operation::PauseUnpause => self.handle_pause_unpause(),
-                Operation::MousePosition { x, y } => {
-                    self.handle_move(x, y, delta * self.state.simualtion_speed.get_speed())
+                Operation::MousePosition { x, y } => self.handle_mouse_position(
+                    *x,
+                    *y,
+                    delta * self.state.simualtion_speed.get_value(),
+                ),
+                Operation::MouseDown { x, y } => {
+                    let location = self.screen_to_world(*x, *y);
+                    self.handle_mouse_down(&location.x, &location.y)
+                }
+                Operation::MouseUp { x, y } => {
+                    let location = self.screen_to_world(*x, *y);
+                    self.handle_mouse_up(&location.x, &location.y)
+                }
+                Operation::PanStart { x, y } => self.handle_pan_start(*x, *y),
+                Operation::PanEnd { x, y } => self.handle_pan_end(*x, *y),
+                Operation::Pan { dx, dy } => self.handle_pan(*dx, *dy),
+                Operation::Zoom { factor, x, y } => self.handle_zoom(*factor, *x, *y),
+                Operation::ResetCamera => self.handle_reset_camera(),
+                Operation::SelectStart { x, y } => {
+                    let location = self.screen_to_world(*x, *y);
+                    self.handle_select_start(&location.x, &location.y)
                 }
-                Operation::MouseDown { x, y } => self.handle_mouse_down(x, y),
-                Operation::MouseUp { x, y } => self.handle_mouse_up(x, y),
-                Operation::Remove { x, y } => self.handle_right_click(x, y),
+                Operation::ClearSelection => self.handle_clear_selection(),
+                Operation::Delete => self.handle_delete(),
+                Operation::ToggleStaticSelection => self.handle_toggle_static_selection(),
+                Operation::GroupSelection => self.handle_group_selection(),
+                Operation::ToggleCollidingSelection => self.handle_toggle_colliding_selection(),
+                Operation::ToggleSkipConnectedCollisions => {
+                    self.handle_toggle_skip_connected_collisions()
+                }
+                Operation::ToggleEdgeCollision => self.handle_toggle_edge_collision(),
+                Operation::ZeroVelocitySelection => self.handle_zero_velocity_selection(),
                 Operation::ToggleDebug => self.handle_toggle_debug(),
-                Operation::DragStart { x, y } => self.handle_drag_start(x, y),
+                Operation::DragStart { x, y } => {
+                    let location = self.screen_to_world(*x, *y);
+                    self.handle_drag_start(&location.x, &location.y)
+                }
                 Operation::DragEnd => self.handle_drag_end(),
                 Operation::IncreaseSimulationSpeed => self.handle_increase_simulation_speed(),
                 Operation::DecreaseSimulationSpeed => self.handle_decrease_simulation_speed(),
+                Operation::IncreasePointMass => self.handle_increase_point_mass(),
+                Operation::DecreasePointMass => self.handle_decrease_point_mass(),
+                Operation::IncreasePointCharge => self.handle_increase_point_charge(),
+                Operation::DecreasePointCharge => self.handle_decrease_point_charge(),
+                Operation::IncreasePointRadius => self.handle_increase_point_radius(),
+                Operation::DecreasePointRadius => self.handle_decrease_point_radius(),
+                Operation::IncreaseConstraintStiffness => {
+                    self.handle_increase_constraint_stiffness()
+                }
+                Operation::DecreaseConstraintStiffness => {
+                    self.handle_decrease_constraint_stiffness()
+                }
+                Operation::IncreaseConstraintBreakThreshold => {
+                    self.handle_increase_constraint_break_threshold()
+                }
+                Operation::DecreaseConstraintBreakThreshold => {
+                    self.handle_decrease_constraint_break_threshold()
+                }
+                Operation::ToggleBreakOnForce => self.handle_toggle_break_on_force(),
+                Operation::CycleConstraintKind => self.handle_cycle_constraint_kind(),
+                Operation::ToggleIntegrator => self.handle_toggle_integrator(),
+                Operation::CycleTool => self.handle_cycle_tool(),
+                Operation::FinishShape => self.handle_finish_shape(),
+                Operation::IncreaseToolSetting => self.handle_increase_tool_setting(),
+                Operation::DecreaseToolSetting => self.handle_decrease_tool_setting(),
+                Operation::TogglePrefabPinned => self.handle_toggle_prefab_pinned(),
+                Operation::Copy => self.handle_copy(),
+                Operation::Paste => self.handle_paste(),
+                Operation::Duplicate => self.handle_duplicate(),
+                Operation::Undo => self.handle_undo(),
+                Operation::Redo => self.handle_redo(),
+                Operation::SaveScene => self.handle_save_scene(),
+                Operation::LoadScene => self.handle_load_scene(),
+                Operation::ToggleBroadPhase => self.handle_toggle_broad_phase(),
+                Operation::ToggleUniformGravity => self.handle_toggle_uniform_gravity(),
+                Operation::CycleNBodyMode => self.handle_cycle_n_body_mode(),
             }
         }
     }
 
+    /// Starts stepping afresh from the current system, so no time left over from before is
+    /// simulated on it and nothing is interpolated from locations it never had
+    fn reset_time_step(&mut self) {
+        self.state.accumulator = 0.0;
+        self.store_previous_locations();
+    }
+
+    fn store_previous_locations(&mut self) {
+        self.previous_locations.clear();
+        self.previous_locations.extend(
+            self.physics_system
+                .get_points_ids()
+                .iter()
+                .map(|(id, point)| (*id, point.location)),
+        );
+    }
+
+    fn handle_simulation_events(&mut self, events: Vec<SimulationEvent>) {
+        for event in events {
+            match event {
+                SimulationEvent::ConstraintBroken { from, to } => {
+                    self.state.broken_constraints_count += 1;
+                    self.state
+                        .broken_constraint_flashes
+                        .push(BrokenConstraintFlash {
+                            from,
+                            to,
+                            time_left: self.ui_renderer.get_broken_constraint_flash_time(),
+                        });
+                }
+                // Interpolating from the far side would streak the point across the box
+                SimulationEvent::PointWrapped { id } => {
+                    self.previous_locations.remove(&id);
+                }
+                SimulationEvent::PointRemoved { id } => {
+                    self.previous_locations.remove(&id);
+                    self.state.selection.remove(&id);
+                }
+            }
+        }
+    }
+
+    fn update_broken_constraint_flashes(&mut self, delta: f32) {
+        for flash in &mut self.state.broken_constraint_flashes {
+            flash.time_left -= delta;
+        }
+        self.state
+            .broken_constraint_flashes
+            .retain(|flash| flash.time_left > 0.0);
+    }
+
     pub fn next_step(&mut self, delta: f32) {
-        if !self.state.is_paused {
-            self.simulator.next_step(
-                &mut self.physics_system,
-                delta * self.state.simualtion_speed.get_speed(),
-            );
+        self.update_broken_constraint_flashes(delta);
+        if self.state.is_paused {
+            return;
+        }
+
+        let time_step = self.time_step_params.time_step;
+        self.state.accumulator += delta * self.state.simualtion_speed.get_value();
+
+        let steps = ((self.state.accumulator / time_step) as usize)
+            .min(self.time_step_params.max_steps_per_frame);
+        for step in 0..steps {
+            if step + 1 == steps {
+                self.store_previous_locations();
+            }
+            let events = self
+                .simulator
+                .next_step(&mut self.physics_system, time_step);
+            self.handle_simulation_events(events);
+            self.state.accumulator -= time_step;
+        }
+
+        if self.state.accumulator >= time_step {
+            self.state.accumulator %= time_step;
+        }
+    }
+
+    fn get_interpolation_alpha(&self) -> f32 {
+        if self.state.is_paused {
+            1.0
+        } else {
+            self.state.accumulator / self.time_step_params.time_step
         }
     }
 
     fn draw_ui_constraint_line(&self, screen_size: (f32, f32)) {
-        if self.state.selected_point.is_none() || self.state.is_draging {
+        let Some(id) = self.state.constraint_start else {
             return;
-        }
+        };
 
-        let point = self
-            .physics_system
-            .get_point(self.state.selected_point.unwrap());
+        let point = self.physics_system.get_point(id);
         if point.is_none() {
             return;
         }
@@ -252,42 +1359,175 @@ impl Controller {
         self.ui_renderer.draw_line(
             vec2(self.state.mouse_pos.0, self.state.mouse_pos.1),
             point.unwrap().location,
+            &self.camera,
             screen_size,
         );
     }
 
-    fn draw_debug_window(&self, screen_size: (f32, f32)) {
-        let option_id =
-            self.find_point_id_for_location(self.state.mouse_pos.0, self.state.mouse_pos.1);
-        if option_id.is_none() {
+    /// Outlines the obstacle being drawn, up to the cursor
+    fn draw_obstacle_preview(&self, screen_size: (f32, f32)) {
+        let mouse_pos = vec2(self.state.mouse_pos.0, self.state.mouse_pos.1);
+        let mut vertices = self.state.obstacle_vertices.clone();
+        if let (Tool::Obstacle(ObstacleTool::Segment), Some(gesture_start)) =
+            (self.state.tool, self.state.gesture_start)
+        {
+            vertices.push(gesture_start);
+        }
+        if vertices.is_empty() {
             return;
         }
-        let id = option_id.unwrap();
-        let option_point = self.physics_system.get_point(id);
-        if option_point.is_none() {
+        vertices.push(mouse_pos);
+
+        for pair in vertices.windows(2) {
+            self.ui_renderer
+                .draw_line(pair[0], pair[1], &self.camera, screen_size);
+        }
+    }
+
+    fn draw_force_field_preview(&self, screen_size: (f32, f32)) {
+        let Some(gesture_start) = self.state.gesture_start else {
             return;
+        };
+        let mouse_pos = vec2(self.state.mouse_pos.0, self.state.mouse_pos.1);
+
+        if let Some(force_field) = self.build_force_field(gesture_start, mouse_pos) {
+            self.renderer
+                .draw_force_field(&force_field, &self.camera, screen_size);
         }
-        let point = option_point.unwrap();
+    }
 
-        let draw_at = (self.state.mouse_pos.0, self.state.mouse_pos.1 + 0.02);
-        self.ui_renderer
-            .draw_point_info(screen_size, id, point, draw_at);
+    fn draw_prefab_preview(&self, screen_size: (f32, f32)) {
+        let Some(gesture_start) = self.state.gesture_start else {
+            return;
+        };
+        let mouse_pos = vec2(self.state.mouse_pos.0, self.state.mouse_pos.1);
+        let Some(preview) = self.build_prefab(gesture_start, mouse_pos) else {
+            return;
+        };
+
+        for constraint in preview.get_constraints() {
+            let (Some(point1), Some(point2)) = (
+                preview.get_point(constraint.get_point1()),
+                preview.get_point(constraint.get_point2()),
+            ) else {
+                continue;
+            };
+            self.ui_renderer
+                .draw_line(point1.location, point2.location, &self.camera, screen_size);
+        }
+    }
+
+    fn draw_debug_window(&self, screen_size: (f32, f32)) {
+        let draw_at = self.state.mouse_screen_pos + vec2(0.0, 0.02 * screen_size.1);
+        let (x, y) = self.state.mouse_pos;
+
+        if let Some(id) = self.find_point_id_for_location(x, y) {
+            if let Some(point) = self.physics_system.get_point(id) {
+                self.ui_renderer
+                    .draw_point_info(screen_size, &self.camera, id, point, draw_at);
+            }
+            return;
+        }
+
+        let Some((point1, point2)) = self.get_hovered_constraint() else {
+            return;
+        };
+        let (Some(constraint), Some(from), Some(to)) = (
+            self.physics_system.find_constraint(point1, point2),
+            self.physics_system.get_point(point1),
+            self.physics_system.get_point(point2),
+        ) else {
+            return;
+        };
+
+        self.ui_renderer.draw_constraint_info(
+            screen_size,
+            constraint,
+            from.location.distance(to.location),
+            draw_at,
+        );
     }
 
     pub fn draw_frame(&self) {
-        self.renderer.draw(&self.physics_system);
+        self.renderer.draw(
+            &self.physics_system,
+            self.simulator.get_bounding_box(),
+            &self.camera,
+            self.simulator.get_params().spring_coeff,
+            &Interpolation {
+                previous_locations: &self.previous_locations,
+                alpha: self.get_interpolation_alpha(),
+            },
+            &Highlights {
+                hovered_constraint: self.get_hovered_constraint(),
+                selected_constraint: self.state.selected_constraint,
+                selected_points: &self.state.selection,
+            },
+        );
         let screen_size = screen_size();
 
         self.draw_ui_constraint_line(screen_size);
+        self.draw_prefab_preview(screen_size);
+        self.draw_obstacle_preview(screen_size);
+        self.draw_force_field_preview(screen_size);
+        if let Some(marquee_start) = self.state.marquee_start {
+            self.ui_renderer.draw_selection_rectangle(
+                marquee_start,
+                vec2(self.state.mouse_pos.0, self.state.mouse_pos.1),
+                &self.camera,
+                screen_size,
+            );
+        }
         self.ui_renderer
-            .draw_simulation_speed(screen_size, self.state.simualtion_speed.get_speed());
+            .draw_simulation_speed(screen_size, self.state.simualtion_speed.get_value());
+        self.ui_renderer.draw_point_placement(
+            screen_size,
+            &PlacementInfo {
+                mass: self.state.point_mass.get_value(),
+                radius: self.get_placement_radius(),
+                charge: self.state.point_charge.get_value(),
+                stiffness: self
+                    .get_placement_stiffness()
+                    .unwrap_or(self.simulator.get_params().spring_coeff),
+                break_threshold: self.get_placement_break_threshold(),
+                constraint_kind: self.state.constraint_kind,
+            },
+        );
+        self.ui_renderer.draw_tool(
+            screen_size,
+            self.state.tool,
+            self.state.prefab_segments.get_value() as usize,
+            self.state.is_prefab_pinned,
+            self.state.force_field_strength_scale.get_value(),
+            self.state.is_edge_colliding,
+        );
+        for flash in &self.state.broken_constraint_flashes {
+            self.ui_renderer.draw_broken_constraint(
+                flash.from,
+                flash.to,
+                flash.time_left,
+                &self.camera,
+                screen_size,
+            );
+        }
 
         if self.state.is_debug_mode {
             self.ui_renderer.draw_debug_text(
                 screen_size,
-                self.state.mouse_pos,
-                self.physics_system.get_points_ids().len(),
-                self.physics_system.get_constraints().len(),
+                &DebugInfo {
+                    mouse_pos: self.state.mouse_pos,
+                    points: self.physics_system.get_points_ids().len(),
+                    constraints: self.physics_system.get_constraints().len(),
+                    broad_phase: self.simulator.get_broad_phase(),
+                    integrator: self.simulator.get_integrator(),
+                    is_uniform_gravity: self.simulator.get_params().is_uniform_gravity,
+                    n_body_mode: self.simulator.get_params().n_body.mode,
+                    is_skipping_connected_collisions: self
+                        .simulator
+                        .get_params()
+                        .skip_connected_collisions,
+                    broken_constraints: self.state.broken_constraints_count,
+                },
             );
             self.draw_debug_window(screen_size);
         }
@@ -0,0 +1,35 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{math::get_softened_inverse_square, point::Point};
+
+/// Electrostatic forces between charged points
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CoulombParams {
+    pub coulomb_constant: f32,
+    /// Distance beyond which charges stop acting on each other
+    pub cutoff: f32,
+    /// Keeps the force between overlapping charges finite
+    pub softening: f32,
+}
+impl Default for CoulombParams {
+    fn default() -> Self {
+        Self {
+            coulomb_constant: 0.0005,
+            cutoff: 0.2,
+            softening: 0.01,
+        }
+    }
+}
+impl CoulombParams {
+    /// Force of `other` on `point`, pushing them apart when their charges have the same sign
+    pub fn get_force(&self, point: &Point, other: &Point) -> Vec2 {
+        let offset = point.location - other.location;
+        if offset.length_squared() >= self.cutoff * self.cutoff {
+            return Vec2::ZERO;
+        }
+
+        get_softened_inverse_square(offset, self.softening)
+            * (self.coulomb_constant * point.charge * other.charge)
+    }
+}
@@ -0,0 +1,90 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::point::Point;
+
+/// Fraction of the field radius inside which an inverse square field stops growing
+const INVERSE_SQUARE_CORE: f32 = 0.1;
+
+/// How the pull of a radial field changes with the distance from its center
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Falloff {
+    /// Same strength everywhere inside the radius
+    Constant,
+    /// Full strength at the center down to zero at the radius
+    Linear,
+    /// Full strength within the core, then falling with the square of the distance
+    InverseSquare,
+}
+impl Falloff {
+    /// Multiplier of the strength at `distance` from the center of a field of `radius`
+    fn get_scale(self, distance: f32, radius: f32) -> f32 {
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => 1.0 - distance / radius,
+            Falloff::InverseSquare => {
+                let core = INVERSE_SQUARE_CORE * radius;
+                (core / distance.max(core)).powi(2)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForceFieldKind {
+    /// Pulls points towards the center, or pushes them away with a negative strength
+    Radial { falloff: Falloff },
+    /// Pushes points along the unit `direction`, lighter points more than heavy ones
+    Wind { direction: Vec2 },
+    /// Swirls points around the center, clockwise on screen with a positive strength and
+    /// fading out towards the radius
+    Vortex,
+}
+
+/// Circular region that accelerates the points inside it
+#[derive(Debug, Clone)]
+pub struct ForceField {
+    pub kind: ForceFieldKind,
+    pub center: Vec2,
+    pub radius: f32,
+    /// Peak acceleration, or for wind the force, so lighter points are pushed harder
+    pub strength: f32,
+}
+impl ForceField {
+    pub fn new(kind: ForceFieldKind, center: Vec2, radius: f32, strength: f32) -> Self {
+        Self {
+            kind,
+            center,
+            radius,
+            strength,
+        }
+    }
+
+    pub fn contains(&self, location: Vec2) -> bool {
+        location.distance(self.center) < self.radius
+    }
+
+    pub fn get_acceleration(&self, point: &Point) -> Vec2 {
+        let offset = self.center - point.location;
+        let distance = offset.length();
+        if distance >= self.radius {
+            return Vec2::ZERO;
+        }
+
+        match self.kind {
+            ForceFieldKind::Radial { falloff } => {
+                offset.normalize_or_zero()
+                    * self.strength
+                    * falloff.get_scale(distance, self.radius)
+            }
+            ForceFieldKind::Wind { direction } => {
+                direction * self.strength * point.get_inverse_mass()
+            }
+            ForceFieldKind::Vortex => {
+                -offset.perp().normalize_or_zero()
+                    * self.strength
+                    * Falloff::Linear.get_scale(distance, self.radius)
+            }
+        }
+    }
+}
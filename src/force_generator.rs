@@ -0,0 +1,340 @@
+use glam::{vec2, Vec2};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    constraint::ConstraintKind,
+    n_body::{NBodyMode, QuadTree},
+    physics_system::PhysicsSystem,
+    point::Point,
+    simulator::{BroadPhase, CollisionPairFilter, Integrator, SimulationParams, Simulator},
+    spatial_hash::SpatialHash,
+};
+
+pub const GRAVITY: &str = "gravity";
+pub const N_BODY_GRAVITY: &str = "n_body_gravity";
+pub const COULOMB: &str = "coulomb";
+pub const FORCE_FIELDS: &str = "force_fields";
+pub const SPRINGS: &str = "springs";
+pub const COLLISIONS: &str = "collisions";
+pub const DRAG: &str = "drag";
+
+/// A source of forces run once per step, before locations are integrated
+pub trait ForceGenerator: Send + Sync {
+    /// Identifies the generator when reordering or disabling it
+    fn get_name(&self) -> &str;
+
+    /// Velocity changes over a step of `delta` as `(point id, change)`, computed from the
+    /// current state without modifying it. Changes of static points are ignored
+    fn get_velocity_changes(
+        &self,
+        physics_system: &PhysicsSystem,
+        params: &SimulationParams,
+        delta: f32,
+    ) -> Vec<(u64, Vec2)>;
+}
+
+/// Runs `velocity_change` over every moving point in parallel, for generators that act on each
+/// point on its own
+pub fn get_point_velocity_changes(
+    physics_system: &PhysicsSystem,
+    velocity_change: impl Fn(&Point) -> Vec2 + Sync,
+) -> Vec<(u64, Vec2)> {
+    physics_system
+        .get_points_ids()
+        .par_iter()
+        .filter(|(_id, point)| !point.is_static)
+        .map(|(id, point)| (*id, velocity_change(point)))
+        .filter(|(_id, change)| *change != Vec2::ZERO)
+        .collect()
+}
+
+/// Uniform downward acceleration of `params.gravity`, unless `params.is_uniform_gravity` is off
+pub struct Gravity;
+impl ForceGenerator for Gravity {
+    fn get_name(&self) -> &str {
+        GRAVITY
+    }
+
+    fn get_velocity_changes(
+        &self,
+        physics_system: &PhysicsSystem,
+        params: &SimulationParams,
+        delta: f32,
+    ) -> Vec<(u64, Vec2)> {
+        if !params.is_uniform_gravity {
+            return vec![];
+        }
+
+        get_point_velocity_changes(physics_system, |_point| vec2(0.0, params.gravity * delta))
+    }
+}
+
+/// Mutual attraction of all points as set by `params.n_body`. Static points pull on the others
+/// without moving
+pub struct NBodyGravity;
+impl ForceGenerator for NBodyGravity {
+    fn get_name(&self) -> &str {
+        N_BODY_GRAVITY
+    }
+
+    fn get_velocity_changes(
+        &self,
+        physics_system: &PhysicsSystem,
+        params: &SimulationParams,
+        delta: f32,
+    ) -> Vec<(u64, Vec2)> {
+        let n_body = &params.n_body;
+        let points = physics_system.get_points_ids();
+        match n_body.mode {
+            NBodyMode::Off => vec![],
+            NBodyMode::Direct => {
+                let ids = physics_system.get_sorted_ids();
+                get_point_velocity_changes(physics_system, |point| {
+                    n_body.get_direct_acceleration(point.location, ids, points) * delta
+                })
+            }
+            NBodyMode::BarnesHut => {
+                let tree = QuadTree::new(physics_system.get_sorted_ids(), points);
+                get_point_velocity_changes(physics_system, |point| {
+                    tree.get_acceleration(point.location, n_body) * delta
+                })
+            }
+        }
+    }
+}
+
+/// Attraction and repulsion between charged points closer than `params.coulomb.cutoff`,
+/// found through a spatial hash unless the broad phase checks all pairs
+pub struct Coulomb;
+impl ForceGenerator for Coulomb {
+    fn get_name(&self) -> &str {
+        COULOMB
+    }
+
+    fn get_velocity_changes(
+        &self,
+        physics_system: &PhysicsSystem,
+        params: &SimulationParams,
+        delta: f32,
+    ) -> Vec<(u64, Vec2)> {
+        let coulomb = &params.coulomb;
+        let points = physics_system.get_points_ids();
+        if coulomb.cutoff <= 0.0 || !points.values().any(|point| point.charge != 0.0) {
+            return vec![];
+        }
+
+        let ids = physics_system.get_sorted_ids();
+        let spatial_hash = match params.broad_phase {
+            BroadPhase::AllPairs => None,
+            BroadPhase::SpatialHash => Some(SpatialHash::new(coulomb.cutoff, physics_system)),
+        };
+
+        get_point_velocity_changes(physics_system, |point| {
+            if point.charge == 0.0 {
+                return Vec2::ZERO;
+            }
+
+            let get_force = |other: &Point| {
+                if other.charge == 0.0 {
+                    Vec2::ZERO
+                } else {
+                    coulomb.get_force(point, other)
+                }
+            };
+            let force: Vec2 = match &spatial_hash {
+                None => ids.iter().map(|id| get_force(&points[id])).sum(),
+                Some(spatial_hash) => spatial_hash
+                    .get_nearby_ids(point.location, coulomb.cutoff)
+                    .map(|other_id| get_force(&points[&other_id]))
+                    .sum(),
+            };
+
+            force * point.get_inverse_mass() * delta
+        })
+    }
+}
+
+/// The force fields stored in the system
+pub struct ForceFields;
+impl ForceGenerator for ForceFields {
+    fn get_name(&self) -> &str {
+        FORCE_FIELDS
+    }
+
+    fn get_velocity_changes(
+        &self,
+        physics_system: &PhysicsSystem,
+        _params: &SimulationParams,
+        delta: f32,
+    ) -> Vec<(u64, Vec2)> {
+        let force_fields = physics_system.get_force_fields();
+        if force_fields.is_empty() {
+            return vec![];
+        }
+
+        get_point_velocity_changes(physics_system, |point| {
+            force_fields
+                .iter()
+                .map(|force_field| force_field.get_acceleration(point))
+                .sum::<Vec2>()
+                * delta
+        })
+    }
+}
+
+/// Damped Hooke forces of springs, ropes and struts. Only used by the semi-implicit Euler
+/// integrator, XPBD solves constraints as position corrections instead, see
+/// `SimulationParams::solve_springs`
+pub struct Springs;
+impl ForceGenerator for Springs {
+    fn get_name(&self) -> &str {
+        SPRINGS
+    }
+
+    fn get_velocity_changes(
+        &self,
+        physics_system: &PhysicsSystem,
+        params: &SimulationParams,
+        delta: f32,
+    ) -> Vec<(u64, Vec2)> {
+        if params.integrator != Integrator::SemiImplicitEuler {
+            return vec![];
+        }
+
+        physics_system
+            .get_constraints()
+            .par_iter()
+            .filter(|c| c.get_kind() != ConstraintKind::Rod)
+            .flat_map_iter(|c| {
+                let point1 = physics_system
+                    .get_point(c.get_point1())
+                    .expect("Invalid constraint: point should be some");
+                let point2 = physics_system
+                    .get_point(c.get_point2())
+                    .expect("Invalid constraint: point should be some");
+
+                let direction = point2.location - point1.location;
+                let distance_between_points = direction.length();
+                let direction_norm = direction.normalize_or_zero();
+                let displacement = distance_between_points - c.get_distance();
+                if c.get_kind().is_slack(displacement) {
+                    return vec![];
+                }
+
+                let force = c.get_stiffness_or(params.spring_coeff) * displacement;
+                let relative_velocity = point2.velocity - point1.velocity;
+                let damping =
+                    c.get_damping_or(params.damping) * relative_velocity.dot(direction_norm);
+                let impulse = (force + damping) * direction_norm * delta;
+
+                vec![
+                    (c.get_point1(), impulse * point1.get_inverse_mass()),
+                    (c.get_point2(), -impulse * point2.get_inverse_mass()),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Penalty forces between overlapping points that pass the collision filters. Only used by the
+/// semi-implicit Euler integrator, XPBD solves contacts as position corrections instead, see
+/// `SimulationParams::solve_contacts`
+pub struct Collisions;
+impl Collisions {
+    fn calculate_collision(
+        point1: &Point,
+        point2: &Point,
+        params: &SimulationParams,
+        delta: f32,
+    ) -> Vec2 {
+        let direction = point1.location - point2.location;
+        let distance = direction.length();
+        let collision_distance = point1.radius + point2.radius;
+        if distance >= collision_distance {
+            return vec2(0.0, 0.0);
+        }
+
+        let force = (collision_distance - distance) * params.collision_force * delta;
+
+        direction.normalize_or_zero() * force
+    }
+}
+impl ForceGenerator for Collisions {
+    fn get_name(&self) -> &str {
+        COLLISIONS
+    }
+
+    fn get_velocity_changes(
+        &self,
+        physics_system: &PhysicsSystem,
+        params: &SimulationParams,
+        delta: f32,
+    ) -> Vec<(u64, Vec2)> {
+        if params.integrator != Integrator::SemiImplicitEuler {
+            return vec![];
+        }
+
+        let points = physics_system.get_points_ids();
+        let pair_filter = CollisionPairFilter::new(physics_system, params);
+        let ids = physics_system.get_sorted_ids();
+        let spatial_hash = match params.broad_phase {
+            BroadPhase::AllPairs => None,
+            BroadPhase::SpatialHash => Some(Simulator::build_spatial_hash(physics_system)),
+        };
+
+        points
+            .par_iter()
+            .filter(|(_id, point)| !point.is_static)
+            .map(|(id, point)| {
+                let mut impulse = vec2(0.0, 0.0);
+                let mut add_collision = |other_id: &u64| {
+                    let other_point = points.get(other_id).expect("Point should be found");
+                    if pair_filter.can_collide(*id, point, *other_id, other_point) {
+                        impulse += Self::calculate_collision(point, other_point, params, delta);
+                    }
+                };
+                match &spatial_hash {
+                    None => ids.iter().for_each(add_collision),
+                    Some((spatial_hash, max_radius)) => spatial_hash
+                        .get_nearby_ids(point.location, point.radius + max_radius)
+                        .for_each(|other_id| add_collision(&other_id)),
+                }
+
+                (*id, impulse * point.get_inverse_mass())
+            })
+            .filter(|(_id, change)| *change != Vec2::ZERO)
+            .collect()
+    }
+}
+
+/// Air resistance, keeping `params.air_resistence` of the velocity per unit of time
+pub struct Drag;
+impl ForceGenerator for Drag {
+    fn get_name(&self) -> &str {
+        DRAG
+    }
+
+    fn get_velocity_changes(
+        &self,
+        physics_system: &PhysicsSystem,
+        params: &SimulationParams,
+        delta: f32,
+    ) -> Vec<(u64, Vec2)> {
+        let kept = params.air_resistence.powf(delta);
+
+        get_point_velocity_changes(physics_system, |point| point.velocity * (kept - 1.0))
+    }
+}
+
+/// The built-in generators in their default order
+pub fn get_stock_generators() -> Vec<Box<dyn ForceGenerator>> {
+    vec![
+        Box::new(Gravity),
+        Box::new(NBodyGravity),
+        Box::new(Coulomb),
+        Box::new(ForceFields),
+        Box::new(Springs),
+        Box::new(Collisions),
+        Box::new(Drag),
+    ]
+}
@@ -0,0 +1,477 @@
+use std::collections::VecDeque;
+
+use glam::{vec2, Vec2};
+
+use crate::{
+    constraint::Constraint,
+    force_field::ForceField,
+    obstacle::Obstacle,
+    physics_system::PhysicsSystem,
+    point::{CollisionFilter, Point},
+};
+
+/// An edit that has already been applied to a `PhysicsSystem` and can be reverted
+#[derive(Debug, Clone)]
+pub enum EditCommand {
+    AddPoint {
+        id: u64,
+        point: Point,
+    },
+    RemovePoint {
+        id: u64,
+        point: Point,
+        constraints: Vec<Constraint>,
+    },
+    AddConstraint {
+        constraint: Constraint,
+    },
+    RemoveConstraint {
+        constraint: Constraint,
+    },
+    ToggleStatic {
+        id: u64,
+    },
+    ToggleEdgeCollision {
+        point1: u64,
+        point2: u64,
+    },
+    SetCollisionFilter {
+        id: u64,
+        from: CollisionFilter,
+        to: CollisionFilter,
+    },
+    MovePoint {
+        id: u64,
+        from: Vec2,
+        to: Vec2,
+    },
+    SetVelocity {
+        id: u64,
+        from: Vec2,
+        to: Vec2,
+    },
+    AddObstacle {
+        index: usize,
+        obstacle: Obstacle,
+    },
+    RemoveObstacle {
+        index: usize,
+        obstacle: Obstacle,
+    },
+    AddForceField {
+        index: usize,
+        force_field: ForceField,
+    },
+    RemoveForceField {
+        index: usize,
+        force_field: ForceField,
+    },
+    MoveForceField {
+        index: usize,
+        from: Vec2,
+        to: Vec2,
+    },
+    /// Several edits undone and redone as one, reverted in reverse order
+    Batch {
+        commands: Vec<EditCommand>,
+    },
+}
+impl EditCommand {
+    fn toggle_static(physics_system: &mut PhysicsSystem, id: u64) {
+        if let Some(point) = physics_system.get_point_mut(id) {
+            point.is_static = !point.is_static;
+        }
+    }
+
+    fn toggle_edge_collision(physics_system: &mut PhysicsSystem, point1: u64, point2: u64) {
+        if let Some(constraint) = physics_system.find_constraint_mut(point1, point2) {
+            constraint.set_colliding(!constraint.is_colliding());
+        }
+    }
+
+    fn set_collision_filter(physics_system: &mut PhysicsSystem, id: u64, filter: CollisionFilter) {
+        if let Some(point) = physics_system.get_point_mut(id) {
+            point.collision_filter = filter;
+        }
+    }
+
+    fn move_point(physics_system: &mut PhysicsSystem, id: u64, location: Vec2) {
+        if let Some(point) = physics_system.get_point_mut(id) {
+            point.location = location;
+            point.velocity = vec2(0.0, 0.0);
+        }
+    }
+
+    fn set_velocity(physics_system: &mut PhysicsSystem, id: u64, velocity: Vec2) {
+        if let Some(point) = physics_system.get_point_mut(id) {
+            point.velocity = velocity;
+        }
+    }
+
+    fn move_force_field(physics_system: &mut PhysicsSystem, index: usize, center: Vec2) {
+        if let Some(force_field) = physics_system.get_force_field_mut(index) {
+            force_field.center = center;
+        }
+    }
+
+    /// Constraints are only restored if both of their points still exist
+    fn add_constraint(physics_system: &mut PhysicsSystem, constraint: &Constraint) {
+        let is_connected = physics_system.get_point(constraint.get_point1()).is_some()
+            && physics_system.get_point(constraint.get_point2()).is_some();
+        if is_connected {
+            physics_system.add_constraint(constraint.clone());
+        }
+    }
+
+    fn apply(&mut self, physics_system: &mut PhysicsSystem) {
+        match self {
+            EditCommand::AddPoint { id, point } => physics_system.insert_point(*id, point.clone()),
+            EditCommand::RemovePoint {
+                id,
+                point,
+                constraints,
+            } => {
+                if let Some((current, removed)) = physics_system.remove_point(*id) {
+                    *point = current;
+                    *constraints = removed;
+                }
+            }
+            EditCommand::AddConstraint { constraint } => {
+                Self::add_constraint(physics_system, constraint)
+            }
+            EditCommand::RemoveConstraint { constraint } => {
+                physics_system.remove_constraint(constraint.get_point1(), constraint.get_point2());
+            }
+            EditCommand::ToggleStatic { id } => Self::toggle_static(physics_system, *id),
+            EditCommand::ToggleEdgeCollision { point1, point2 } => {
+                Self::toggle_edge_collision(physics_system, *point1, *point2)
+            }
+            EditCommand::SetCollisionFilter { id, to, .. } => {
+                Self::set_collision_filter(physics_system, *id, *to)
+            }
+            EditCommand::MovePoint { id, to, .. } => Self::move_point(physics_system, *id, *to),
+            EditCommand::SetVelocity { id, to, .. } => Self::set_velocity(physics_system, *id, *to),
+            EditCommand::AddObstacle { index, obstacle } => {
+                physics_system.insert_obstacle(*index, obstacle.clone())
+            }
+            EditCommand::RemoveObstacle { index, obstacle } => {
+                if let Some(current) = physics_system.remove_obstacle(*index) {
+                    *obstacle = current;
+                }
+            }
+            EditCommand::AddForceField { index, force_field } => {
+                physics_system.insert_force_field(*index, force_field.clone())
+            }
+            EditCommand::RemoveForceField { index, force_field } => {
+                if let Some(current) = physics_system.remove_force_field(*index) {
+                    *force_field = current;
+                }
+            }
+            EditCommand::MoveForceField { index, to, .. } => {
+                Self::move_force_field(physics_system, *index, *to)
+            }
+            EditCommand::Batch { commands } => {
+                for command in commands.iter_mut() {
+                    command.apply(physics_system);
+                }
+            }
+        }
+    }
+
+    fn revert(&mut self, physics_system: &mut PhysicsSystem) {
+        match self {
+            EditCommand::AddPoint { id, point } => {
+                if let Some((current, _constraints)) = physics_system.remove_point(*id) {
+                    *point = current;
+                }
+            }
+            EditCommand::RemovePoint {
+                id,
+                point,
+                constraints,
+            } => {
+                physics_system.insert_point(*id, point.clone());
+                for constraint in constraints.iter() {
+                    Self::add_constraint(physics_system, constraint);
+                }
+            }
+            EditCommand::AddConstraint { constraint } => {
+                physics_system.remove_constraint(constraint.get_point1(), constraint.get_point2());
+            }
+            EditCommand::RemoveConstraint { constraint } => {
+                Self::add_constraint(physics_system, constraint)
+            }
+            EditCommand::ToggleStatic { id } => Self::toggle_static(physics_system, *id),
+            EditCommand::ToggleEdgeCollision { point1, point2 } => {
+                Self::toggle_edge_collision(physics_system, *point1, *point2)
+            }
+            EditCommand::SetCollisionFilter { id, from, .. } => {
+                Self::set_collision_filter(physics_system, *id, *from)
+            }
+            EditCommand::MovePoint { id, from, .. } => Self::move_point(physics_system, *id, *from),
+            EditCommand::SetVelocity { id, from, .. } => {
+                Self::set_velocity(physics_system, *id, *from)
+            }
+            EditCommand::AddObstacle { index, obstacle } => {
+                if let Some(current) = physics_system.remove_obstacle(*index) {
+                    *obstacle = current;
+                }
+            }
+            EditCommand::RemoveObstacle { index, obstacle } => {
+                physics_system.insert_obstacle(*index, obstacle.clone())
+            }
+            EditCommand::AddForceField { index, force_field } => {
+                if let Some(current) = physics_system.remove_force_field(*index) {
+                    *force_field = current;
+                }
+            }
+            EditCommand::RemoveForceField { index, force_field } => {
+                physics_system.insert_force_field(*index, force_field.clone())
+            }
+            EditCommand::MoveForceField { index, from, .. } => {
+                Self::move_force_field(physics_system, *index, *from)
+            }
+            EditCommand::Batch { commands } => {
+                for command in commands.iter_mut().rev() {
+                    command.revert(physics_system);
+                }
+            }
+        }
+    }
+}
+
+pub struct History {
+    max_depth: usize,
+    undo_stack: VecDeque<EditCommand>,
+    redo_stack: Vec<EditCommand>,
+}
+impl History {
+    /// The undo stack grows as edits are recorded, so a large `max_depth` costs nothing upfront
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            undo_stack: VecDeque::new(),
+            redo_stack: vec![],
+        }
+    }
+
+    /// Records an edit that was just applied, dropping the oldest one past `max_depth`
+    pub fn record(&mut self, command: EditCommand) {
+        self.redo_stack.clear();
+        if self.max_depth == 0 {
+            return;
+        }
+        if self.undo_stack.len() == self.max_depth {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(command);
+    }
+
+    /// Records edits that were just applied as a single step, nothing is recorded if empty
+    pub fn record_batch(&mut self, mut commands: Vec<EditCommand>) {
+        match commands.len() {
+            0 => {}
+            1 => self.record(commands.pop().expect("Batch should have one command")),
+            _ => self.record(EditCommand::Batch { commands }),
+        }
+    }
+
+    /// Returns false if there was nothing to undo
+    pub fn undo(&mut self, physics_system: &mut PhysicsSystem) -> bool {
+        let Some(mut command) = self.undo_stack.pop_back() else {
+            return false;
+        };
+        command.revert(physics_system);
+        self.redo_stack.push(command);
+
+        true
+    }
+
+    /// Returns false if there was nothing to redo
+    pub fn redo(&mut self, physics_system: &mut PhysicsSystem) -> bool {
+        let Some(mut command) = self.redo_stack.pop() else {
+            return false;
+        };
+        command.apply(physics_system);
+        self.undo_stack.push_back(command);
+
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constraint::ConstraintKind;
+
+    use super::*;
+
+    /// Points by id and constraints by their ends, in an order that does not depend on how the
+    /// system was edited
+    fn get_state(physics_system: &PhysicsSystem) -> (Vec<(u64, Point)>, Vec<Constraint>) {
+        let points = physics_system
+            .get_sorted_ids()
+            .iter()
+            .map(|id| (*id, physics_system.get_point(*id).unwrap().clone()))
+            .collect();
+        let mut constraints = physics_system.get_constraints().to_vec();
+        constraints.sort_by_key(|c| (c.get_point1(), c.get_point2()));
+
+        (points, constraints)
+    }
+
+    fn get_constraint(point1: u64, point2: u64) -> Constraint {
+        Constraint::new(
+            point1,
+            point2,
+            0.1,
+            ConstraintKind::Spring,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Three points at rest in a row with the first two connected
+    fn get_physics_system() -> PhysicsSystem {
+        let mut physics_system = PhysicsSystem::new();
+        for i in 0..3 {
+            physics_system.add_point(Point::new(
+                vec2(0.1 * i as f32, 0.5),
+                Vec2::ZERO,
+                false,
+                0.01,
+                1.0,
+            ));
+        }
+        physics_system.add_constraint(get_constraint(0, 1));
+
+        physics_system
+    }
+
+    fn apply_and_record(
+        history: &mut History,
+        physics_system: &mut PhysicsSystem,
+        mut command: EditCommand,
+    ) {
+        command.apply(physics_system);
+        history.record(command);
+    }
+
+    fn get_move(id: u64, from: Vec2, to: Vec2) -> EditCommand {
+        EditCommand::MovePoint { id, from, to }
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_system() {
+        let point = Point::new(vec2(0.7, 0.7), Vec2::ZERO, true, 0.02, 2.0);
+        let commands = [
+            EditCommand::AddPoint { id: 3, point },
+            EditCommand::RemovePoint {
+                id: 1,
+                point: Point::new(Vec2::ZERO, Vec2::ZERO, false, 0.01, 1.0),
+                constraints: vec![],
+            },
+            get_move(2, vec2(0.2, 0.5), vec2(0.4, 0.3)),
+            EditCommand::AddConstraint {
+                constraint: get_constraint(0, 2),
+            },
+            EditCommand::RemoveConstraint {
+                constraint: get_constraint(0, 2),
+            },
+        ];
+
+        let mut physics_system = get_physics_system();
+        let mut history = History::new(10);
+        for command in commands {
+            let before = get_state(&physics_system);
+            apply_and_record(&mut history, &mut physics_system, command);
+            let after = get_state(&physics_system);
+            assert_ne!(before, after);
+
+            assert!(history.undo(&mut physics_system));
+            assert_eq!(get_state(&physics_system), before);
+            assert!(history.redo(&mut physics_system));
+            assert_eq!(get_state(&physics_system), after);
+        }
+    }
+
+    #[test]
+    fn batch_reverts_in_reverse_order() {
+        let mut physics_system = get_physics_system();
+        let mut history = History::new(10);
+        apply_and_record(
+            &mut history,
+            &mut physics_system,
+            EditCommand::Batch {
+                commands: vec![
+                    get_move(0, vec2(0.0, 0.5), vec2(0.3, 0.3)),
+                    get_move(0, vec2(0.3, 0.3), vec2(0.6, 0.6)),
+                ],
+            },
+        );
+        assert_eq!(
+            physics_system.get_point(0).unwrap().location,
+            vec2(0.6, 0.6)
+        );
+
+        assert!(history.undo(&mut physics_system));
+        assert_eq!(
+            physics_system.get_point(0).unwrap().location,
+            vec2(0.0, 0.5)
+        );
+        assert!(history.redo(&mut physics_system));
+        assert_eq!(
+            physics_system.get_point(0).unwrap().location,
+            vec2(0.6, 0.6)
+        );
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut physics_system = get_physics_system();
+        let mut history = History::new(10);
+        apply_and_record(
+            &mut history,
+            &mut physics_system,
+            get_move(0, vec2(0.0, 0.5), vec2(0.3, 0.3)),
+        );
+        assert!(history.undo(&mut physics_system));
+
+        apply_and_record(
+            &mut history,
+            &mut physics_system,
+            get_move(1, vec2(0.1, 0.5), vec2(0.4, 0.4)),
+        );
+        assert!(!history.redo(&mut physics_system));
+        assert_eq!(
+            physics_system.get_point(0).unwrap().location,
+            vec2(0.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn depth_cap_drops_the_oldest_edit() {
+        let mut physics_system = get_physics_system();
+        let mut history = History::new(2);
+        for (from, to) in [(0.0, 0.1), (0.1, 0.2), (0.2, 0.3)] {
+            apply_and_record(
+                &mut history,
+                &mut physics_system,
+                get_move(0, vec2(from, 0.5), vec2(to, 0.5)),
+            );
+        }
+
+        assert!(history.undo(&mut physics_system));
+        assert!(history.undo(&mut physics_system));
+        assert!(!history.undo(&mut physics_system));
+        assert_eq!(
+            physics_system.get_point(0).unwrap().location,
+            vec2(0.1, 0.5)
+        );
+    }
+}
@@ -1,23 +1,91 @@
 use macroquad::input::{
-    get_char_pressed, is_key_released, is_mouse_button_pressed, is_mouse_button_released,
-    mouse_position,
+    get_char_pressed, is_key_down, is_key_released, is_mouse_button_pressed,
+    is_mouse_button_released, mouse_position, mouse_wheel,
 };
 
+const KEYBOARD_PAN_SPEED: f32 = 8.0;
+const ZOOM_STEP: f32 = 1.1;
+
+/// Positions are in screen pixels, the controller maps them to world coordinates
 #[derive(Debug)]
 pub enum Operation {
     PauseUnpause,
     MousePosition { x: f32, y: f32 },
     MouseDown { x: f32, y: f32 },
     MouseUp { x: f32, y: f32 },
-    Remove { x: f32, y: f32 },
+    PanStart { x: f32, y: f32 },
+    PanEnd { x: f32, y: f32 },
+    Pan { dx: f32, dy: f32 },
+    Zoom { factor: f32, x: f32, y: f32 },
+    ResetCamera,
+    ClearSelection,
+    Delete,
+    ToggleStaticSelection,
+    ToggleEdgeCollision,
+    GroupSelection,
+    ToggleCollidingSelection,
+    ToggleSkipConnectedCollisions,
+    ZeroVelocitySelection,
+    SelectStart { x: f32, y: f32 },
     DragStart { x: f32, y: f32 },
     DragEnd,
     ToggleDebug,
     IncreaseSimulationSpeed,
     DecreaseSimulationSpeed,
+    IncreasePointMass,
+    DecreasePointMass,
+    IncreasePointCharge,
+    DecreasePointCharge,
+    IncreasePointRadius,
+    DecreasePointRadius,
+    IncreaseConstraintStiffness,
+    DecreaseConstraintStiffness,
+    IncreaseConstraintBreakThreshold,
+    DecreaseConstraintBreakThreshold,
+    ToggleBreakOnForce,
+    CycleConstraintKind,
+    ToggleBroadPhase,
+    ToggleUniformGravity,
+    CycleNBodyMode,
+    ToggleIntegrator,
+    CycleTool,
+    FinishShape,
+    IncreaseToolSetting,
+    DecreaseToolSetting,
+    TogglePrefabPinned,
+    Copy,
+    Paste,
+    Duplicate,
+    Undo,
+    Redo,
+    SaveScene,
+    LoadScene,
+}
+
+fn get_keyboard_pan() -> Option<Operation> {
+    let axis = |negative, positive| {
+        let mut value = 0.0;
+        if is_key_down(negative) {
+            value -= KEYBOARD_PAN_SPEED;
+        }
+        if is_key_down(positive) {
+            value += KEYBOARD_PAN_SPEED;
+        }
+        value
+    };
+    let dx = axis(
+        macroquad::input::KeyCode::Right,
+        macroquad::input::KeyCode::Left,
+    );
+    let dy = axis(
+        macroquad::input::KeyCode::Down,
+        macroquad::input::KeyCode::Up,
+    );
+
+    (dx != 0.0 || dy != 0.0).then_some(Operation::Pan { dx, dy })
 }
 
-pub fn get_input(screen_size: (f32, f32)) -> Vec<Operation> {
+pub fn get_input() -> Vec<Operation> {
     let mut operations = vec![];
 
     let char_pressed = get_char_pressed();
@@ -26,30 +94,38 @@ pub fn get_input(screen_size: (f32, f32)) -> Vec<Operation> {
     }
 
     let mouse_pos = mouse_position();
-    let normalised_position = (mouse_pos.0 / screen_size.0, mouse_pos.1 / screen_size.1);
+    let is_shift_down = is_key_down(macroquad::input::KeyCode::LeftShift)
+        || is_key_down(macroquad::input::KeyCode::RightShift);
+    let is_control_down = is_key_down(macroquad::input::KeyCode::LeftControl)
+        || is_key_down(macroquad::input::KeyCode::RightControl);
     operations.push(Operation::MousePosition {
-        x: normalised_position.0,
-        y: normalised_position.1,
+        x: mouse_pos.0,
+        y: mouse_pos.1,
     });
 
-    if is_mouse_button_pressed(macroquad::input::MouseButton::Left) {
+    if is_shift_down && is_mouse_button_pressed(macroquad::input::MouseButton::Left) {
+        operations.push(Operation::SelectStart {
+            x: mouse_pos.0,
+            y: mouse_pos.1,
+        });
+    } else if is_mouse_button_pressed(macroquad::input::MouseButton::Left) {
         operations.push(Operation::MouseDown {
-            x: normalised_position.0,
-            y: normalised_position.1,
+            x: mouse_pos.0,
+            y: mouse_pos.1,
         });
     }
 
     if is_mouse_button_released(macroquad::input::MouseButton::Left) {
         operations.push(Operation::MouseUp {
-            x: normalised_position.0,
-            y: normalised_position.1,
+            x: mouse_pos.0,
+            y: mouse_pos.1,
         });
     }
 
     if is_mouse_button_pressed(macroquad::input::MouseButton::Right) {
         operations.push(Operation::DragStart {
-            x: normalised_position.0,
-            y: normalised_position.1,
+            x: mouse_pos.0,
+            y: mouse_pos.1,
         });
     }
 
@@ -57,24 +133,192 @@ pub fn get_input(screen_size: (f32, f32)) -> Vec<Operation> {
         operations.push(Operation::DragEnd);
     }
 
+    if is_mouse_button_pressed(macroquad::input::MouseButton::Middle) {
+        operations.push(Operation::PanStart {
+            x: mouse_pos.0,
+            y: mouse_pos.1,
+        });
+    }
+
     if is_mouse_button_released(macroquad::input::MouseButton::Middle) {
-        operations.push(Operation::Remove {
-            x: normalised_position.0,
-            y: normalised_position.1,
+        operations.push(Operation::PanEnd {
+            x: mouse_pos.0,
+            y: mouse_pos.1,
+        });
+    }
+
+    let wheel = mouse_wheel().1;
+    if wheel != 0.0 {
+        operations.push(Operation::Zoom {
+            factor: ZOOM_STEP.powf(wheel.signum()),
+            x: mouse_pos.0,
+            y: mouse_pos.1,
         });
     }
 
-    if is_key_released(macroquad::input::KeyCode::D) {
+    if is_key_released(macroquad::input::KeyCode::Home) {
+        operations.push(Operation::ResetCamera);
+    }
+
+    if is_shift_down {
+        operations.extend(get_keyboard_pan());
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Escape) {
+        operations.push(Operation::ClearSelection);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Delete) {
+        operations.push(Operation::Delete);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::T) {
+        operations.push(Operation::ToggleStaticSelection);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::E) {
+        operations.push(Operation::ToggleEdgeCollision);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::R) {
+        operations.push(Operation::GroupSelection);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::O) {
+        operations.push(Operation::ToggleCollidingSelection);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::X) {
+        operations.push(Operation::ToggleSkipConnectedCollisions);
+    }
+
+    if !is_control_down && is_key_released(macroquad::input::KeyCode::V) {
+        operations.push(Operation::ZeroVelocitySelection);
+    }
+
+    if !is_control_down && is_key_released(macroquad::input::KeyCode::D) {
         operations.push(Operation::ToggleDebug);
     }
 
-    if is_key_released(macroquad::input::KeyCode::Left) {
+    if !is_shift_down && is_key_released(macroquad::input::KeyCode::Left) {
         operations.push(Operation::DecreaseSimulationSpeed);
     }
 
-    if is_key_released(macroquad::input::KeyCode::Right) {
+    if !is_shift_down && is_key_released(macroquad::input::KeyCode::Right) {
         operations.push(Operation::IncreaseSimulationSpeed);
     }
 
+    if !is_shift_down && is_key_released(macroquad::input::KeyCode::Up) {
+        operations.push(Operation::IncreasePointMass);
+    }
+
+    if !is_shift_down && is_key_released(macroquad::input::KeyCode::Down) {
+        operations.push(Operation::DecreasePointMass);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Q) {
+        operations.push(Operation::IncreasePointCharge);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::A) {
+        operations.push(Operation::DecreasePointCharge);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::RightBracket) {
+        operations.push(Operation::IncreasePointRadius);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::LeftBracket) {
+        operations.push(Operation::DecreasePointRadius);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Period) {
+        operations.push(Operation::IncreaseConstraintStiffness);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Comma) {
+        operations.push(Operation::DecreaseConstraintStiffness);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Apostrophe) {
+        operations.push(Operation::IncreaseConstraintBreakThreshold);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Semicolon) {
+        operations.push(Operation::DecreaseConstraintBreakThreshold);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::F) {
+        operations.push(Operation::ToggleBreakOnForce);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::K) {
+        operations.push(Operation::CycleConstraintKind);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::P) {
+        operations.push(Operation::CycleTool);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Enter) {
+        operations.push(Operation::FinishShape);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Equal) {
+        operations.push(Operation::IncreaseToolSetting);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::Minus) {
+        operations.push(Operation::DecreaseToolSetting);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::H) {
+        operations.push(Operation::TogglePrefabPinned);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::B) {
+        operations.push(Operation::ToggleBroadPhase);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::I) {
+        operations.push(Operation::ToggleIntegrator);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::G) {
+        operations.push(Operation::ToggleUniformGravity);
+    }
+
+    if is_key_released(macroquad::input::KeyCode::N) {
+        operations.push(Operation::CycleNBodyMode);
+    }
+
+    if is_control_down && is_key_released(macroquad::input::KeyCode::C) {
+        operations.push(Operation::Copy);
+    }
+
+    if is_control_down && is_key_released(macroquad::input::KeyCode::V) {
+        operations.push(Operation::Paste);
+    }
+
+    if is_control_down && is_key_released(macroquad::input::KeyCode::D) {
+        operations.push(Operation::Duplicate);
+    }
+
+    if is_control_down && is_key_released(macroquad::input::KeyCode::Z) {
+        operations.push(Operation::Undo);
+    }
+
+    if is_control_down && is_key_released(macroquad::input::KeyCode::Y) {
+        operations.push(Operation::Redo);
+    }
+
+    if is_control_down && is_key_released(macroquad::input::KeyCode::S) {
+        operations.push(Operation::SaveScene);
+    }
+
+    if is_control_down && is_key_released(macroquad::input::KeyCode::L) {
+        operations.push(Operation::LoadScene);
+    }
+
     operations
 }
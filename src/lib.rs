@@ -0,0 +1,32 @@
+//! Physics core of the simulation, with the macroquad front end behind the `gui` feature
+
+pub use glam::{vec2, Vec2};
+
+pub mod constraint;
+pub mod coulomb;
+pub mod force_field;
+pub mod force_generator;
+pub mod history;
+pub mod math;
+pub mod n_body;
+pub mod obstacle;
+pub mod physics_system;
+pub mod point;
+pub mod prefab;
+pub mod scene;
+pub mod simulation_config;
+pub mod simulator;
+pub mod spatial_hash;
+
+#[cfg(feature = "gui")]
+pub mod camera;
+#[cfg(feature = "gui")]
+pub mod config;
+#[cfg(feature = "gui")]
+pub mod controller;
+#[cfg(feature = "gui")]
+pub mod input;
+#[cfg(feature = "gui")]
+pub mod renderer;
+#[cfg(feature = "gui")]
+pub mod ui_renderer;
@@ -1,22 +1,18 @@
-use config::Config;
-use controller::Controller;
-use input::get_input;
 use macroquad::prelude::*;
-use miniquad::window::screen_size;
-use physics_system::PhysicsSystem;
-use renderer::{DrawParams, Renderer};
-use simulator::{SimulationBoundingBox, SimulationParams, Simulator};
-use ui_renderer::{UiParams, UiRenderer};
-
-mod config;
-mod constraint;
-mod controller;
-mod input;
-mod physics_system;
-mod point;
-mod renderer;
-mod simulator;
-mod ui_renderer;
+use physics::{
+    config::Config,
+    controller::{Controller, EditorParams, TimeStepParams},
+    coulomb::CoulombParams,
+    force_field::Falloff,
+    input::get_input,
+    n_body::{NBodyMode, NBodyParams},
+    physics_system::PhysicsSystem,
+    renderer::{DrawParams, Renderer},
+    simulator::{
+        BroadPhase, Integrator, SimulationBoundingBox, SimulationParams, Simulator, Wall, WallKind,
+    },
+    ui_renderer::{UiParams, UiRenderer},
+};
 
 const CONFIG_PATH: &str = "config.toml";
 
@@ -24,32 +20,68 @@ fn construct_default_controller() -> Controller {
     let point_size = 0.015;
     let line_size = 0.005;
     let physics_system = PhysicsSystem::new();
+    let wall = Wall {
+        kind: WallKind::Solid,
+        restitution: 0.3,
+        friction: 0.2,
+    };
     let simulator = Simulator::new(
         SimulationParams {
             air_resistence: 0.96,
             gravity: 0.09,
+            is_uniform_gravity: true,
+            n_body: NBodyParams {
+                mode: NBodyMode::Off,
+                gravitational_constant: 0.001,
+                softening: 0.01,
+                theta: 0.5,
+            },
+            coulomb: CoulombParams {
+                coulomb_constant: 0.0005,
+                cutoff: 0.2,
+                softening: 0.01,
+            },
             point_size,
             spring_coeff: 550.0,
             damping: 6.0,
             collision_force: 100.0,
-            push_from_sides_force: 0.02,
+            skip_connected_collisions: false,
+            broad_phase: BroadPhase::SpatialHash,
+            rod_iterations: 8,
+            integrator: Integrator::SemiImplicitEuler,
+            solver_iterations: 10,
+            solve_springs: true,
+            solve_contacts: true,
+            parallel_stages: false,
         },
         SimulationBoundingBox {
             max_x: 1.0,
             max_y: 1.0,
             min_x: 0.0,
             min_y: 0.0,
+            left: wall,
+            right: wall,
+            top: wall,
+            bottom: wall,
         },
     );
     let renderer = Renderer::new(DrawParams {
         bg_color: Color::from_rgba(10, 10, 40, 255),
-        point_size,
         line_size,
         point_color: Color::from_rgba(50, 255, 50, 255),
         point_border_color: Color::from_rgba(255, 255, 255, 255),
         static_point_color: Color::from_rgba(255, 50, 50, 255),
         line_color: Color::from_rgba(255, 255, 255, 255),
         stressed_line_color: Color::from_rgba(255, 0, 0, 255),
+        rod_line_color: Color::from_rgba(180, 180, 255, 255),
+        bounding_box_color: Color::from_rgba(60, 60, 100, 255),
+        hover_color: Color::from_rgba(255, 255, 255, 90),
+        selection_color: Color::from_rgba(80, 200, 255, 160),
+        obstacle_color: Color::from_rgba(200, 160, 110, 255),
+        obstacle_fill_color: Color::from_rgba(90, 70, 50, 255),
+        force_field_color: Color::from_rgba(200, 120, 255, 200),
+        positive_charge_color: Color::from_rgba(255, 170, 50, 255),
+        negative_charge_color: Color::from_rgba(60, 160, 255, 255),
     });
     let ui_renderer = UiRenderer::new(UiParams {
         paused_text_location: (0.45, 0.08),
@@ -69,27 +101,48 @@ fn construct_default_controller() -> Controller {
         speed_text_location: (0.01, 0.98),
         speed_text_size: 0.04,
         speed_text_color: WHITE,
+        placement_text_location: (0.2, 0.98),
+        tool_text_location: (0.01, 0.94),
+        broken_line_color: Color::from_rgba(255, 200, 40, 255),
+        broken_line_flash_time: 0.5,
     });
 
-    Controller::new(physics_system, simulator, renderer, ui_renderer)
+    let time_step_params = TimeStepParams {
+        time_step: 1.0 / 240.0,
+        max_steps_per_frame: 16,
+    };
+    let editor_params = EditorParams {
+        history_depth: 100,
+        obstacle_restitution: 0.3,
+        obstacle_friction: 0.4,
+        force_field_strength: 0.3,
+        wind_strength: 0.1,
+        force_field_falloff: Falloff::InverseSquare,
+    };
+
+    Controller::new(
+        physics_system,
+        simulator,
+        renderer,
+        ui_renderer,
+        time_step_params,
+        editor_params,
+    )
 }
 
 fn init_controller() -> Controller {
-    let config = Config::load(CONFIG_PATH);
-
-    if config.is_err() {
-        println!(
-            "Error loading config file '{CONFIG_PATH}': {}",
-            config.unwrap_err()
-        );
-        println!("Using default settings");
+    match Config::load(CONFIG_PATH) {
+        Ok(config_params) => {
+            println!("Loaded config file '{CONFIG_PATH}'");
 
-        construct_default_controller()
-    } else {
-        let config_params = config.unwrap();
-        println!("Loaded config file '{CONFIG_PATH}'");
+            config_params.into()
+        }
+        Err(err) => {
+            println!("Error loading config file '{CONFIG_PATH}': {err}");
+            println!("Using default settings");
 
-        config_params.into()
+            construct_default_controller()
+        }
     }
 }
 
@@ -99,9 +152,8 @@ async fn main() {
 
     loop {
         let delta = get_frame_time();
-        let screen_size = screen_size();
 
-        controller.handle_input(&get_input(screen_size), delta);
+        controller.handle_input(&get_input(), delta);
         controller.next_step(delta);
         controller.draw_frame();
 
@@ -0,0 +1,29 @@
+use glam::Vec2;
+
+/// Position of the point of the segment from `from` to `to` closest to `location`, as a fraction
+/// of the way along it from 0 to 1. `None` for a segment of zero length
+pub(crate) fn project_onto_segment(location: Vec2, from: Vec2, to: Vec2) -> Option<f32> {
+    let segment = to - from;
+    let length_squared = segment.length_squared();
+    if length_squared == 0.0 {
+        return None;
+    }
+
+    Some(((location - from).dot(segment) / length_squared).clamp(0.0, 1.0))
+}
+
+/// Point of the segment from `from` to `to` closest to `location`
+pub(crate) fn closest_point_on_segment(location: Vec2, from: Vec2, to: Vec2) -> Vec2 {
+    from.lerp(to, project_onto_segment(location, from, to).unwrap_or(0.0))
+}
+
+/// Inverse-square field of a unit source at `offset`, `offset / (|offset|² + softening²)^1.5`,
+/// shared by gravity and electrostatics. Zero when both the offset and softening are
+pub fn get_softened_inverse_square(offset: Vec2, softening: f32) -> Vec2 {
+    let distance_squared = offset.length_squared() + softening * softening;
+    if distance_squared == 0.0 {
+        return Vec2::ZERO;
+    }
+
+    offset / (distance_squared * distance_squared.sqrt())
+}
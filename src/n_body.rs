@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{math::get_softened_inverse_square, point::Point};
+
+/// Nodes this deep hold all of their points together, so coincident points cannot split forever
+const MAX_TREE_DEPTH: usize = 32;
+const NODES_STARTING_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NBodyMode {
+    Off,
+    /// Sums the pull of every point on every other, O(n²)
+    Direct,
+    /// Approximates distant groups of points by their center of mass, O(n log n)
+    BarnesHut,
+}
+impl NBodyMode {
+    pub fn next(self) -> Self {
+        match self {
+            NBodyMode::Off => NBodyMode::Direct,
+            NBodyMode::Direct => NBodyMode::BarnesHut,
+            NBodyMode::BarnesHut => NBodyMode::Off,
+        }
+    }
+}
+
+/// Mutual inverse-square gravitation between points
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct NBodyParams {
+    pub mode: NBodyMode,
+    pub gravitational_constant: f32,
+    /// Length added in quadrature to distances, keeping close encounters finite
+    pub softening: f32,
+    /// Largest ratio of a node's size to its distance that is approximated as a single mass
+    pub theta: f32,
+}
+impl Default for NBodyParams {
+    fn default() -> Self {
+        Self {
+            mode: NBodyMode::Off,
+            gravitational_constant: 0.001,
+            softening: 0.01,
+            theta: 0.5,
+        }
+    }
+}
+impl NBodyParams {
+    /// Acceleration towards `mass` at `offset`, zero for a point pulling on itself
+    fn get_softened_acceleration(&self, offset: Vec2, mass: f32) -> Vec2 {
+        get_softened_inverse_square(offset, self.softening) * (self.gravitational_constant * mass)
+    }
+
+    /// Pull of the `points` listed in `ids` on `location`, summed one by one
+    pub fn get_direct_acceleration(
+        &self,
+        location: Vec2,
+        ids: &[u64],
+        points: &HashMap<u64, Point>,
+    ) -> Vec2 {
+        ids.iter()
+            .map(|id| &points[id])
+            .filter(|other| other.mass > 0.0)
+            .map(|other| self.get_softened_acceleration(other.location - location, other.mass))
+            .sum()
+    }
+}
+
+struct Node {
+    min: Vec2,
+    size: f32,
+    mass: f32,
+    /// Mass-weighted sum of locations until the tree is built, then the center of mass
+    center_of_mass: Vec2,
+    /// Location and mass of the single point of a leaf
+    point: Option<(Vec2, f32)>,
+    children: Option<[usize; 4]>,
+}
+impl Node {
+    fn new(min: Vec2, size: f32) -> Self {
+        Self {
+            min,
+            size,
+            mass: 0.0,
+            center_of_mass: Vec2::ZERO,
+            point: None,
+            children: None,
+        }
+    }
+
+    fn contains(&self, location: Vec2) -> bool {
+        location.cmpge(self.min).all() && location.cmple(self.min + self.size).all()
+    }
+
+    fn get_quadrant(&self, location: Vec2) -> usize {
+        let middle = self.min + self.size / 2.0;
+        usize::from(location.x >= middle.x) + 2 * usize::from(location.y >= middle.y)
+    }
+}
+
+/// Barnes–Hut quadtree of point masses
+pub struct QuadTree {
+    nodes: Vec<Node>,
+}
+impl QuadTree {
+    /// Builds the tree from the `points` listed in `ids`, inserted in that order
+    pub fn new(ids: &[u64], points: &HashMap<u64, Point>) -> Self {
+        let points: Vec<_> = ids.iter().map(|id| &points[id]).collect();
+        let (min, max) = points.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), point| (min.min(point.location), max.max(point.location)),
+        );
+        let size = (max - min).max_element().max(f32::EPSILON);
+
+        let mut tree = Self {
+            nodes: Vec::with_capacity(NODES_STARTING_CAPACITY),
+        };
+        tree.nodes.push(Node::new(min, size));
+        for point in points.iter().filter(|point| point.mass > 0.0) {
+            tree.insert(point.location, point.mass);
+        }
+        for node in &mut tree.nodes {
+            if node.mass > 0.0 {
+                node.center_of_mass /= node.mass;
+            }
+        }
+
+        tree
+    }
+
+    fn split(&mut self, index: usize) -> [usize; 4] {
+        let half = self.nodes[index].size / 2.0;
+        let min = self.nodes[index].min;
+        let first = self.nodes.len();
+        for quadrant in 0..4 {
+            let offset = Vec2::new((quadrant % 2) as f32, (quadrant / 2) as f32) * half;
+            self.nodes.push(Node::new(min + offset, half));
+        }
+
+        let children = [first, first + 1, first + 2, first + 3];
+        self.nodes[index].children = Some(children);
+        children
+    }
+
+    fn insert(&mut self, location: Vec2, mass: f32) {
+        let mut index = 0;
+        for depth in 0.. {
+            let node = &mut self.nodes[index];
+            let is_empty = node.mass == 0.0;
+            node.mass += mass;
+            node.center_of_mass += location * mass;
+
+            if is_empty {
+                node.point = Some((location, mass));
+                return;
+            }
+            if depth == MAX_TREE_DEPTH {
+                node.point = None;
+                return;
+            }
+
+            let children = match node.children {
+                Some(children) => children,
+                None => {
+                    let previous = node.point.take();
+                    let children = self.split(index);
+                    if let Some((previous_location, previous_mass)) = previous {
+                        let child = children[self.nodes[index].get_quadrant(previous_location)];
+                        let child = &mut self.nodes[child];
+                        child.mass = previous_mass;
+                        child.center_of_mass = previous_location * previous_mass;
+                        child.point = Some((previous_location, previous_mass));
+                    }
+                    children
+                }
+            };
+            index = children[self.nodes[index].get_quadrant(location)];
+        }
+    }
+
+    /// Pull of the tree on `location`, opening nodes that look larger than `params.theta`. Nodes
+    /// containing `location` are always opened, so a point is never pulled by its own mass
+    pub fn get_acceleration(&self, location: Vec2, params: &NBodyParams) -> Vec2 {
+        let mut acceleration = Vec2::ZERO;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.mass == 0.0 {
+                continue;
+            }
+
+            let offset = node.center_of_mass - location;
+            match node.children {
+                Some(children)
+                    if node.contains(location) || node.size > params.theta * offset.length() =>
+                {
+                    stack.extend(children);
+                }
+                _ => acceleration += params.get_softened_acceleration(offset, node.mass),
+            }
+        }
+
+        acceleration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use super::*;
+
+    fn get_params(theta: f32) -> NBodyParams {
+        NBodyParams {
+            mode: NBodyMode::BarnesHut,
+            theta,
+            ..NBodyParams::default()
+        }
+    }
+
+    /// Points scattered over the unit square with masses from 1 to 3, and their ids in order
+    fn get_points(count: u64) -> (Vec<u64>, HashMap<u64, Point>) {
+        let points = (0..count)
+            .map(|id| {
+                let location = vec2((id * 37 % 101) as f32, (id * 59 % 103) as f32) / 100.0;
+                let point = Point::new(location, Vec2::ZERO, false, 0.01, 1.0 + (id % 3) as f32);
+                (id, point)
+            })
+            .collect();
+
+        ((0..count).collect(), points)
+    }
+
+    #[test]
+    fn small_theta_matches_direct_sum() {
+        let (ids, points) = get_points(200);
+        let params = get_params(0.1);
+        let tree = QuadTree::new(&ids, &points);
+        for point in points.values() {
+            let direct = params.get_direct_acceleration(point.location, &ids, &points);
+            let barnes_hut = tree.get_acceleration(point.location, &params);
+            assert!(
+                direct.distance(barnes_hut) <= 0.01 * direct.length(),
+                "{barnes_hut} differs from {direct}"
+            );
+        }
+    }
+
+    #[test]
+    fn large_theta_does_not_pull_a_point_by_its_own_mass() {
+        let (ids, points) = get_points(2);
+        let params = get_params(1e6);
+        let tree = QuadTree::new(&ids, &points);
+        for point in points.values() {
+            let direct = params.get_direct_acceleration(point.location, &ids, &points);
+            let barnes_hut = tree.get_acceleration(point.location, &params);
+            assert!(
+                direct.distance(barnes_hut) <= 1e-6 * direct.length(),
+                "{barnes_hut} differs from {direct}"
+            );
+        }
+    }
+}
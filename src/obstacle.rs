@@ -0,0 +1,268 @@
+use glam::{vec2, Vec2};
+
+use crate::{math::closest_point_on_segment, point::Point};
+
+/// Geometry of a static collider, in world coordinates
+#[derive(Debug, Clone)]
+pub enum ObstacleShape {
+    /// Line that points collide with from either side
+    Segment { from: Vec2, to: Vec2 },
+    /// Closed polygon, solid inside
+    Polygon { vertices: Vec<Vec2> },
+    /// Surface with `heights[i]` at `min_x + i * spacing`, solid below (towards positive y)
+    Heightmap {
+        min_x: f32,
+        spacing: f32,
+        heights: Vec<f32>,
+    },
+}
+impl ObstacleShape {
+    fn get_edge_count(&self) -> usize {
+        match self {
+            ObstacleShape::Segment { .. } => 1,
+            ObstacleShape::Polygon { vertices } => vertices.len(),
+            ObstacleShape::Heightmap { heights, .. } => heights.len().saturating_sub(1),
+        }
+    }
+
+    fn get_edge(&self, index: usize) -> (Vec2, Vec2) {
+        match self {
+            ObstacleShape::Segment { from, to } => (*from, *to),
+            ObstacleShape::Polygon { vertices } => {
+                (vertices[index], vertices[(index + 1) % vertices.len()])
+            }
+            ObstacleShape::Heightmap {
+                min_x,
+                spacing,
+                heights,
+            } => {
+                let x = min_x + index as f32 * spacing;
+                (
+                    vec2(x, heights[index]),
+                    vec2(x + spacing, heights[index + 1]),
+                )
+            }
+        }
+    }
+
+    pub fn get_edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        (0..self.get_edge_count()).map(|index| self.get_edge(index))
+    }
+
+    /// Corners of the box around the shape, a heightmap's reaching down without end as
+    /// everything below its surface is solid
+    pub fn get_bounds(&self) -> (Vec2, Vec2) {
+        let (min, max) = self.get_edges().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), (from, to)| (min.min(from).min(to), max.max(from).max(to)),
+        );
+        match self {
+            ObstacleShape::Heightmap { .. } => (min, vec2(max.x, f32::INFINITY)),
+            _ => (min, max),
+        }
+    }
+
+    /// Whether the location is inside the solid part, always false for a segment
+    pub fn contains(&self, location: Vec2) -> bool {
+        match self {
+            ObstacleShape::Segment { .. } => false,
+            ObstacleShape::Polygon { .. } => {
+                let mut is_inside = false;
+                for (from, to) in self.get_edges() {
+                    let is_crossing = (from.y > location.y) != (to.y > location.y);
+                    if is_crossing
+                        && location.x
+                            < from.x + (location.y - from.y) / (to.y - from.y) * (to.x - from.x)
+                    {
+                        is_inside = !is_inside;
+                    }
+                }
+                is_inside
+            }
+            ObstacleShape::Heightmap { .. } => self
+                .get_height(location.x)
+                .is_some_and(|height| location.y > height),
+        }
+    }
+
+    /// Resamples a polyline into a heightmap every `sample_spacing` along x, `None` if the
+    /// vertices span no width
+    pub fn heightmap_from_vertices(vertices: &[Vec2], sample_spacing: f32) -> Option<Self> {
+        let mut sorted = vertices.to_vec();
+        sorted.sort_by(|vertex1, vertex2| vertex1.x.total_cmp(&vertex2.x));
+        let (first, last) = (sorted.first()?, sorted.last()?);
+        let width = last.x - first.x;
+        if width <= 0.0 {
+            return None;
+        }
+
+        let samples = ((width / sample_spacing).ceil() as usize).max(1);
+        let spacing = width / samples as f32;
+        let heights = (0..=samples)
+            .map(|i| {
+                let x = first.x + i as f32 * spacing;
+                let next = sorted
+                    .partition_point(|vertex| vertex.x < x)
+                    .clamp(1, sorted.len() - 1);
+                let (from, to) = (sorted[next - 1], sorted[next]);
+                if to.x == from.x {
+                    to.y
+                } else {
+                    from.y + (to.y - from.y) * ((x - from.x) / (to.x - from.x)).clamp(0.0, 1.0)
+                }
+            })
+            .collect();
+
+        Some(ObstacleShape::Heightmap {
+            min_x: first.x,
+            spacing,
+            heights,
+        })
+    }
+
+    /// Surface height of a heightmap at `x`, `None` outside of it or for other shapes
+    pub fn get_height(&self, x: f32) -> Option<f32> {
+        let ObstacleShape::Heightmap {
+            min_x,
+            spacing,
+            heights,
+        } = self
+        else {
+            return None;
+        };
+        if heights.len() < 2 {
+            return None;
+        }
+
+        let position = (x - min_x) / spacing;
+        if position < 0.0 || position > (heights.len() - 1) as f32 {
+            return None;
+        }
+        let index = (position as usize).min(heights.len() - 2);
+
+        Some(heights[index] + (heights[index + 1] - heights[index]) * (position - index as f32))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Obstacle {
+    pub shape: ObstacleShape,
+    /// Fraction of the normal velocity kept after a bounce
+    pub restitution: f32,
+    /// Coulomb friction coefficient against the normal impulse
+    pub friction: f32,
+}
+impl Obstacle {
+    pub fn new(shape: ObstacleShape, restitution: f32, friction: f32) -> Self {
+        Self {
+            shape,
+            restitution,
+            friction,
+        }
+    }
+
+    /// Closest point of the outline and its distance to the location
+    pub fn get_closest_point(&self, location: Vec2) -> Option<(Vec2, f32)> {
+        self.shape
+            .get_edges()
+            .map(|(from, to)| {
+                let closest = closest_point_on_segment(location, from, to);
+                (closest, closest.distance(location))
+            })
+            .min_by(|(_, distance1), (_, distance2)| distance1.total_cmp(distance2))
+    }
+
+    /// Outward normal and penetration depth of a circle touching the obstacle
+    fn get_contact(&self, location: Vec2, radius: f32) -> Option<(Vec2, f32)> {
+        let (closest, distance) = self.get_closest_point(location)?;
+
+        if self.shape.contains(location) {
+            let normal = (closest - location).try_normalize()?;
+            return Some((normal, distance + radius));
+        }
+        if distance >= radius {
+            return None;
+        }
+
+        Some(((location - closest).try_normalize()?, radius - distance))
+    }
+
+    /// Pushes the point out of the obstacle and bounces it off with the obstacle's restitution
+    /// and friction
+    pub fn collide(&self, point: &mut Point) {
+        if point.is_static {
+            return;
+        }
+        let Some((normal, depth)) = self.get_contact(point.location, point.radius) else {
+            return;
+        };
+
+        point.resolve_contact(normal, depth, self.restitution, self.friction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f32 = 0.05;
+
+    fn get_point(location: Vec2, velocity: Vec2) -> Point {
+        Point::new(location, velocity, false, RADIUS, 1.0)
+    }
+
+    fn get_square() -> Obstacle {
+        let vertices = vec![
+            vec2(0.0, 0.0),
+            vec2(1.0, 0.0),
+            vec2(1.0, 1.0),
+            vec2(0.0, 1.0),
+        ];
+        Obstacle::new(ObstacleShape::Polygon { vertices }, 0.0, 0.0)
+    }
+
+    fn get_heightmap() -> Obstacle {
+        let shape = ObstacleShape::Heightmap {
+            min_x: 0.0,
+            spacing: 0.5,
+            heights: vec![1.0, 0.8, 1.0],
+        };
+        Obstacle::new(shape, 0.0, 0.0)
+    }
+
+    #[test]
+    fn point_inside_polygon_is_pushed_out_along_nearest_edge() {
+        let obstacle = get_square();
+        let mut point = get_point(vec2(0.4, 0.9), vec2(0.0, -1.0));
+
+        obstacle.collide(&mut point);
+
+        assert!((point.location - vec2(0.4, 1.0 + RADIUS)).length() < 1e-5);
+        assert!(point.velocity.y >= 0.0);
+        assert!(!obstacle.shape.contains(point.location));
+    }
+
+    #[test]
+    fn point_below_heightmap_outside_its_range_is_left_alone() {
+        let obstacle = get_heightmap();
+        let location = vec2(1.5, 2.0);
+        let velocity = vec2(0.0, 1.0);
+        let mut point = get_point(location, velocity);
+
+        assert!(!obstacle.shape.contains(location));
+        obstacle.collide(&mut point);
+
+        assert_eq!(point.location, location);
+        assert_eq!(point.velocity, velocity);
+    }
+
+    #[test]
+    fn point_below_heightmap_next_to_its_end_is_pushed_off_the_corner() {
+        let obstacle = get_heightmap();
+        let mut point = get_point(vec2(1.0 + RADIUS / 2.0, 1.0), Vec2::ZERO);
+
+        obstacle.collide(&mut point);
+
+        assert!((point.location - vec2(1.0 + RADIUS, 1.0)).length() < 1e-5);
+    }
+}
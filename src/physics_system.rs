@@ -1,25 +1,61 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::{constraint::Constraint, point::Point};
+use crate::{constraint::Constraint, force_field::ForceField, obstacle::Obstacle, point::Point};
 
 const POINTS_STARTING_CAPACITY: usize = 16;
 const CONSTRAINTS_STARTING_CAPACITY: usize = 32;
 
-
 pub struct PhysicsSystem {
     id_counter: u64,
     points: HashMap<u64, Point>,
+    /// Ids of `points` in ascending order. Passes over the points that sum floats or solve one
+    /// point after another go through these, so they come out the same on every run instead of
+    /// following the order of the map
+    sorted_ids: Vec<u64>,
     constraints: Vec<Constraint>,
+    obstacles: Vec<Obstacle>,
+    force_fields: Vec<ForceField>,
+}
+impl Default for PhysicsSystem {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl PhysicsSystem {
     pub fn new() -> Self {
         Self {
             id_counter: 0,
             points: HashMap::with_capacity(POINTS_STARTING_CAPACITY),
+            sorted_ids: Vec::with_capacity(POINTS_STARTING_CAPACITY),
             constraints: Vec::with_capacity(CONSTRAINTS_STARTING_CAPACITY),
+            obstacles: vec![],
+            force_fields: vec![],
+        }
+    }
+
+    /// Rebuilds a system from previously stored state, `id_counter` must be above every point id
+    pub fn from_parts(
+        id_counter: u64,
+        points: HashMap<u64, Point>,
+        constraints: Vec<Constraint>,
+    ) -> Self {
+        let mut sorted_ids: Vec<_> = points.keys().copied().collect();
+        sorted_ids.sort_unstable();
+
+        Self {
+            id_counter,
+            points,
+            sorted_ids,
+            constraints,
+            obstacles: vec![],
+            force_fields: vec![],
         }
     }
 
+    pub fn get_id_counter(&self) -> u64 {
+        self.id_counter
+    }
+
     fn new_id(&mut self) -> u64 {
         let id = self.id_counter;
         self.id_counter += 1;
@@ -30,11 +66,29 @@ impl PhysicsSystem {
     pub fn add_point(&mut self, point: Point) -> u64 {
         let id = self.new_id();
         self.points.insert(id, point);
+        // Ids are handed out in increasing order
+        self.sorted_ids.push(id);
 
         id
     }
 
-    pub fn add_constraint(&mut self, constraint: Constraint) {
+    /// Inserts a point under an id handed out earlier, such as when restoring a removed point
+    pub fn insert_point(&mut self, id: u64, point: Point) {
+        self.id_counter = self.id_counter.max(id + 1);
+        if self.points.insert(id, point).is_none() {
+            let index = self.sorted_ids.partition_point(|other| *other < id);
+            self.sorted_ids.insert(index, id);
+        }
+    }
+
+    /// Returns false if the two points were already connected or the distance is not positive
+    /// and finite, as strain is measured against it
+    pub fn add_constraint(&mut self, constraint: Constraint) -> bool {
+        let distance = constraint.get_distance();
+        if !(distance > 0.0 && distance.is_finite()) {
+            return false;
+        }
+
         let constraint_already_exists = self.constraints.iter().any(|c| {
             (c.get_point1() == constraint.get_point1() && c.get_point2() == constraint.get_point2())
                 || (c.get_point1() == constraint.get_point2()
@@ -42,16 +96,22 @@ impl PhysicsSystem {
         });
 
         if constraint_already_exists {
-            return;
+            return false;
         }
 
         self.constraints.push(constraint);
+
+        true
     }
 
     pub fn get_points_ids(&self) -> &HashMap<u64, Point> {
         &self.points
     }
 
+    pub fn get_sorted_ids(&self) -> &[u64] {
+        &self.sorted_ids
+    }
+
     pub fn get_point(&self, id: u64) -> Option<&Point> {
         self.points.get(&id)
     }
@@ -64,15 +124,128 @@ impl PhysicsSystem {
         self.points.iter_mut().map(|(_, point)| point).collect()
     }
 
+    pub fn get_points_ids_mut(&mut self) -> impl Iterator<Item = (u64, &mut Point)> {
+        self.points.iter_mut().map(|(id, point)| (*id, point))
+    }
+
     pub fn get_constraints(&self) -> &[Constraint] {
         &self.constraints
     }
 
-    pub fn remove_point(&mut self, point_id: u64) {
-        self.constraints.retain(|constraint| {
-            constraint.get_point1() != point_id && constraint.get_point2() != point_id
+    pub fn get_obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+
+    /// Points together with the obstacles, so points can be moved out of them
+    pub fn get_points_mut_and_obstacles(&mut self) -> (Vec<&mut Point>, &[Obstacle]) {
+        (self.points.values_mut().collect(), &self.obstacles)
+    }
+
+    /// Returns the index of the new obstacle
+    pub fn add_obstacle(&mut self, obstacle: Obstacle) -> usize {
+        self.obstacles.push(obstacle);
+
+        self.obstacles.len() - 1
+    }
+
+    /// Inserts an obstacle at `index`, clamped to the number of obstacles
+    pub fn insert_obstacle(&mut self, index: usize, obstacle: Obstacle) {
+        self.obstacles
+            .insert(index.min(self.obstacles.len()), obstacle);
+    }
+
+    pub fn remove_obstacle(&mut self, index: usize) -> Option<Obstacle> {
+        (index < self.obstacles.len()).then(|| self.obstacles.remove(index))
+    }
+
+    pub fn get_force_fields(&self) -> &[ForceField] {
+        &self.force_fields
+    }
+
+    pub fn get_force_field_mut(&mut self, index: usize) -> Option<&mut ForceField> {
+        self.force_fields.get_mut(index)
+    }
+
+    /// Returns the index of the new force field
+    pub fn add_force_field(&mut self, force_field: ForceField) -> usize {
+        self.force_fields.push(force_field);
+
+        self.force_fields.len() - 1
+    }
+
+    /// Inserts a force field at `index`, clamped to the number of force fields
+    pub fn insert_force_field(&mut self, index: usize, force_field: ForceField) {
+        self.force_fields
+            .insert(index.min(self.force_fields.len()), force_field);
+    }
+
+    pub fn remove_force_field(&mut self, index: usize) -> Option<ForceField> {
+        (index < self.force_fields.len()).then(|| self.force_fields.remove(index))
+    }
+
+    /// Copies the given points and the constraints that connect only those points
+    pub fn copy_subset(&self, ids: &HashSet<u64>) -> PhysicsSystem {
+        let points = self
+            .points
+            .iter()
+            .filter(|(id, _point)| ids.contains(id))
+            .map(|(id, point)| (*id, point.clone()))
+            .collect();
+        let constraints = self
+            .constraints
+            .iter()
+            .filter(|c| ids.contains(&c.get_point1()) && ids.contains(&c.get_point2()))
+            .cloned()
+            .collect();
+
+        PhysicsSystem::from_parts(self.id_counter, points, constraints)
+    }
+
+    /// Removes the point together with every constraint attached to it, returning both
+    pub fn remove_point(&mut self, point_id: u64) -> Option<(Point, Vec<Constraint>)> {
+        let point = self.points.remove(&point_id)?;
+        let index = self.sorted_ids.partition_point(|other| *other < point_id);
+        self.sorted_ids.remove(index);
+        let (removed, kept) = self.constraints.drain(..).partition(|constraint| {
+            constraint.get_point1() == point_id || constraint.get_point2() == point_id
         });
+        self.constraints = kept;
+
+        Some((point, removed))
+    }
+
+    fn find_constraint_index(&self, point1: u64, point2: u64) -> Option<usize> {
+        self.constraints.iter().position(|c| {
+            (c.get_point1() == point1 && c.get_point2() == point2)
+                || (c.get_point1() == point2 && c.get_point2() == point1)
+        })
+    }
+
+    /// Finds the constraint between the two points, in either order
+    pub fn find_constraint(&self, point1: u64, point2: u64) -> Option<&Constraint> {
+        self.find_constraint_index(point1, point2)
+            .map(|index| &self.constraints[index])
+    }
+
+    /// Finds the constraint between the two points, in either order
+    pub fn find_constraint_mut(&mut self, point1: u64, point2: u64) -> Option<&mut Constraint> {
+        self.find_constraint_index(point1, point2)
+            .map(|index| &mut self.constraints[index])
+    }
+
+    /// Removes the constraint between the two points, in either order
+    pub fn remove_constraint(&mut self, point1: u64, point2: u64) -> Option<Constraint> {
+        let index = self.find_constraint_index(point1, point2)?;
+
+        Some(self.constraints.remove(index))
+    }
 
-        self.points.remove(&point_id);
+    /// Removes the constraints at `indices`, which must be sorted in ascending order
+    pub fn remove_constraints_at(&mut self, indices: &[usize]) -> Vec<Constraint> {
+        indices
+            .iter()
+            .rev()
+            .map(|index| self.constraints.remove(*index))
+            .collect()
     }
 }
@@ -1,17 +1,131 @@
-use macroquad::math::Vec2;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+/// Whether `restitution` and `friction` are usable by `Point::resolve_contact`, a restitution
+/// from 0 to 1 and a finite friction of at least 0
+pub fn is_valid_contact_material(restitution: f32, friction: f32) -> bool {
+    (0.0..=1.0).contains(&restitution) && friction >= 0.0 && friction.is_finite()
+}
+
+/// Which other points a point collides with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollisionFilter {
+    /// Bits of the layers the point belongs to
+    pub layer: u32,
+    /// Bits of the layers the point collides with
+    pub mask: u32,
+    /// Points sharing a non-zero group never collide with each other, zero is no group
+    pub group: u32,
+}
+impl Default for CollisionFilter {
+    fn default() -> Self {
+        Self {
+            layer: 1,
+            mask: u32::MAX,
+            group: 0,
+        }
+    }
+}
+impl CollisionFilter {
+    /// Both points have to be in a layer the other collides with
+    pub fn can_collide(&self, other: &CollisionFilter) -> bool {
+        self.layer & other.mask != 0
+            && other.layer & self.mask != 0
+            && (self.group == 0 || self.group != other.group)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Point {
     pub location: Vec2,
     pub velocity: Vec2,
     pub is_static: bool,
+    pub radius: f32,
+    pub mass: f32,
+    /// Signed charge for the Coulomb stage, like charges repel
+    pub charge: f32,
+    pub collision_filter: CollisionFilter,
 }
 impl Point {
-    pub fn new(location: Vec2, velocity: Vec2, is_static: bool) -> Self {
+    pub fn new(location: Vec2, velocity: Vec2, is_static: bool, radius: f32, mass: f32) -> Self {
         Self {
             location,
             velocity,
             is_static,
+            radius,
+            mass,
+            charge: 0.0,
+            collision_filter: CollisionFilter::default(),
         }
     }
+
+    /// Static points behave as if they had infinite mass
+    pub fn get_inverse_mass(&self) -> f32 {
+        if self.is_static || self.mass <= 0.0 {
+            0.0
+        } else {
+            1.0 / self.mass
+        }
+    }
+
+    /// Moves the point `depth` along the outward `normal` of a static surface and reflects its
+    /// velocity, with friction slowing the tangential part by at most the normal velocity change
+    /// times `friction`
+    pub fn resolve_contact(&mut self, normal: Vec2, depth: f32, restitution: f32, friction: f32) {
+        self.location += normal * depth;
+
+        let normal_speed = self.velocity.dot(normal);
+        if normal_speed >= 0.0 {
+            return;
+        }
+        let normal_change = -(1.0 + restitution) * normal_speed;
+        let tangent_velocity = self.velocity - normal * normal_speed;
+        let tangent_speed = tangent_velocity.length();
+        let friction_change = (friction * normal_change).min(tangent_speed);
+
+        self.velocity += normal * normal_change;
+        if tangent_speed > 0.0 {
+            self.velocity -= tangent_velocity / tangent_speed * friction_change;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_filter(layer: u32, mask: u32, group: u32) -> CollisionFilter {
+        CollisionFilter { layer, mask, group }
+    }
+
+    /// Checks both orders, which must agree
+    fn can_collide(filter1: CollisionFilter, filter2: CollisionFilter) -> bool {
+        let can_collide = filter1.can_collide(&filter2);
+        assert_eq!(can_collide, filter2.can_collide(&filter1));
+        can_collide
+    }
+
+    #[test]
+    fn layers_and_masks_have_to_match_both_ways() {
+        let default = CollisionFilter::default();
+        assert!(can_collide(default, default));
+        assert!(can_collide(
+            get_filter(0b01, 0b10, 0),
+            get_filter(0b10, 0b01, 0)
+        ));
+        assert!(!can_collide(
+            get_filter(0b01, 0b10, 0),
+            get_filter(0b10, 0b10, 0)
+        ));
+        assert!(!can_collide(get_filter(0b01, 0, 0), default));
+        assert!(!can_collide(get_filter(0, u32::MAX, 0), default));
+    }
+
+    #[test]
+    fn shared_non_zero_group_stops_collisions() {
+        assert!(!can_collide(get_filter(1, 1, 3), get_filter(1, 1, 3)));
+        assert!(can_collide(get_filter(1, 1, 3), get_filter(1, 1, 4)));
+        assert!(can_collide(get_filter(1, 1, 3), get_filter(1, 1, 0)));
+        assert!(can_collide(get_filter(1, 1, 0), get_filter(1, 1, 0)));
+    }
 }
@@ -0,0 +1,255 @@
+use std::f32::consts::TAU;
+
+use glam::{vec2, Vec2};
+
+use crate::{
+    constraint::{BreakThreshold, Constraint, ConstraintKind},
+    physics_system::PhysicsSystem,
+    point::Point,
+};
+
+const MIN_WHEEL_RIM_POINTS: usize = 3;
+/// Ragdoll joints as `(right, up)` offsets in body heights from the feet
+const RAGDOLL_JOINTS: [(f32, f32); 14] = [
+    (0.0, 1.0),    // 0 head
+    (0.0, 0.85),   // 1 neck
+    (-0.15, 0.82), // 2 left shoulder
+    (0.15, 0.82),  // 3 right shoulder
+    (-0.3, 0.65),  // 4 left elbow
+    (0.3, 0.65),   // 5 right elbow
+    (-0.4, 0.48),  // 6 left hand
+    (0.4, 0.48),   // 7 right hand
+    (-0.1, 0.5),   // 8 left hip
+    (0.1, 0.5),    // 9 right hip
+    (-0.12, 0.25), // 10 left knee
+    (0.12, 0.25),  // 11 right knee
+    (-0.13, 0.0),  // 12 left foot
+    (0.13, 0.0),   // 13 right foot
+];
+const RAGDOLL_BONES: [(usize, usize); 17] = [
+    (0, 1),
+    (1, 2),
+    (1, 3),
+    (2, 3),
+    (2, 4),
+    (4, 6),
+    (3, 5),
+    (5, 7),
+    (2, 8),
+    (3, 9),
+    (2, 9),
+    (3, 8),
+    (8, 9),
+    (8, 10),
+    (10, 12),
+    (9, 11),
+    (11, 13),
+];
+
+/// A structure placed in one gesture from `from` to `to`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefab {
+    /// Chain of points from `from` to `to`
+    Rope,
+    /// Grid between the two corners with both diagonals in every cell
+    Rectangle,
+    /// Rim around `from` through `to`, with spokes to a hub
+    Wheel,
+    /// Grid between the two corners with one diagonal per cell
+    Cloth,
+    /// Figure standing on `to` with its head at `from`
+    Ragdoll,
+}
+impl Prefab {
+    /// `None` after the last prefab, so the caller can cycle back to plain point placement
+    pub fn next(self) -> Option<Self> {
+        match self {
+            Prefab::Rope => Some(Prefab::Rectangle),
+            Prefab::Rectangle => Some(Prefab::Wheel),
+            Prefab::Wheel => Some(Prefab::Cloth),
+            Prefab::Cloth => Some(Prefab::Ragdoll),
+            Prefab::Ragdoll => None,
+        }
+    }
+
+    /// Builds the prefab as a standalone system, empty if the gesture is shorter than a point
+    pub fn build(self, from: Vec2, to: Vec2, params: &PrefabParams) -> PhysicsSystem {
+        let mut builder = PrefabBuilder::new(params);
+        if from.distance(to) <= params.radius {
+            return builder.system;
+        }
+
+        match self {
+            Prefab::Rope => builder.rope(from, to),
+            Prefab::Rectangle => builder.grid(from, to, true),
+            Prefab::Wheel => builder.wheel(from, to),
+            Prefab::Cloth => builder.grid(from, to, false),
+            Prefab::Ragdoll => builder.ragdoll(from, to),
+        }
+
+        builder.system
+    }
+}
+
+pub struct PrefabParams {
+    /// Segments of a rope, rim points of a wheel and cells along the longer side of a grid
+    pub segments: usize,
+    /// Pins the top row of a cloth and the start of a rope
+    pub is_pinned: bool,
+    pub radius: f32,
+    pub mass: f32,
+    pub charge: f32,
+    pub kind: ConstraintKind,
+    pub stiffness: Option<f32>,
+    pub break_threshold: Option<BreakThreshold>,
+    /// Makes every constraint of the prefab collide with other points
+    pub is_colliding: bool,
+}
+
+struct PrefabBuilder<'a> {
+    params: &'a PrefabParams,
+    /// Shrunk below `params.radius` so neighbouring points do not start out overlapping
+    radius: f32,
+    system: PhysicsSystem,
+}
+impl<'a> PrefabBuilder<'a> {
+    fn new(params: &'a PrefabParams) -> Self {
+        Self {
+            params,
+            radius: params.radius,
+            system: PhysicsSystem::new(),
+        }
+    }
+
+    fn fit_radius(&mut self, spacing: f32) {
+        self.radius = self.params.radius.min(spacing / 2.0);
+    }
+
+    fn point(&mut self, location: Vec2, is_static: bool) -> u64 {
+        let mut point = Point::new(
+            location,
+            vec2(0.0, 0.0),
+            is_static,
+            self.radius,
+            self.params.mass,
+        );
+        point.charge = self.params.charge;
+
+        self.system.add_point(point)
+    }
+
+    fn connect(&mut self, id1: u64, id2: u64) {
+        let (Some(point1), Some(point2)) = (self.system.get_point(id1), self.system.get_point(id2))
+        else {
+            return;
+        };
+
+        let distance = point1.location.distance(point2.location);
+        let mut constraint = Constraint::new(
+            id1,
+            id2,
+            distance,
+            self.params.kind,
+            self.params.stiffness,
+            None,
+            self.params.break_threshold,
+        );
+        constraint.set_colliding(self.params.is_colliding);
+        self.system.add_constraint(constraint);
+    }
+
+    fn rope(&mut self, from: Vec2, to: Vec2) {
+        let segments = self.params.segments.max(1);
+        self.fit_radius(from.distance(to) / segments as f32);
+
+        let ids: Vec<_> = (0..=segments)
+            .map(|i| {
+                let is_static = self.params.is_pinned && i == 0;
+                self.point(from.lerp(to, i as f32 / segments as f32), is_static)
+            })
+            .collect();
+        for pair in ids.windows(2) {
+            self.connect(pair[0], pair[1]);
+        }
+    }
+
+    /// Splits the longer side into `segments` cells and the shorter one into as many square-ish
+    /// cells as fit, pinning the top row of a cloth if asked to. Slivers thinner than a point
+    /// are skipped
+    fn grid(&mut self, corner1: Vec2, corner2: Vec2, is_braced: bool) {
+        let min = corner1.min(corner2);
+        let size = (corner2 - corner1).abs();
+        if size.min_element() <= self.params.radius {
+            return;
+        }
+        let cell_size = size.max_element() / self.params.segments.max(1) as f32;
+        let columns = ((size.x / cell_size).round() as usize).max(1);
+        let rows = ((size.y / cell_size).round() as usize).max(1);
+        let step = vec2(size.x / columns as f32, size.y / rows as f32);
+        self.fit_radius(step.min_element());
+
+        let is_top_pinned = self.params.is_pinned && !is_braced;
+        let ids: Vec<Vec<_>> = (0..=rows)
+            .map(|row| {
+                (0..=columns)
+                    .map(|column| {
+                        let location = min + step * vec2(column as f32, row as f32);
+                        self.point(location, is_top_pinned && row == 0)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for row in 0..=rows {
+            for column in 0..=columns {
+                if column < columns {
+                    self.connect(ids[row][column], ids[row][column + 1]);
+                }
+                if row < rows {
+                    self.connect(ids[row][column], ids[row + 1][column]);
+                }
+                if row < rows && column < columns {
+                    self.connect(ids[row][column], ids[row + 1][column + 1]);
+                    if is_braced {
+                        self.connect(ids[row][column + 1], ids[row + 1][column]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn wheel(&mut self, center: Vec2, rim: Vec2) {
+        let rim_points = self.params.segments.max(MIN_WHEEL_RIM_POINTS);
+        let radius = center.distance(rim);
+        let start_angle = (rim - center).to_angle();
+        self.fit_radius(radius * (TAU / rim_points as f32).min(1.0));
+
+        let hub = self.point(center, false);
+        let ids: Vec<_> = (0..rim_points)
+            .map(|i| {
+                let angle = start_angle + TAU * i as f32 / rim_points as f32;
+                self.point(center + Vec2::from_angle(angle) * radius, false)
+            })
+            .collect();
+
+        for (i, id) in ids.iter().enumerate() {
+            self.connect(*id, ids[(i + 1) % rim_points]);
+            self.connect(hub, *id);
+        }
+    }
+
+    fn ragdoll(&mut self, head: Vec2, feet: Vec2) {
+        let height = head.distance(feet);
+        let up = (head - feet) / height;
+        let right = -up.perp();
+        self.fit_radius(height * 0.1);
+
+        let ids: Vec<_> = RAGDOLL_JOINTS
+            .iter()
+            .map(|(x, y)| self.point(feet + (right * *x + up * *y) * height, false))
+            .collect();
+        for (joint1, joint2) in RAGDOLL_BONES {
+            self.connect(ids[joint1], ids[joint2]);
+        }
+    }
+}
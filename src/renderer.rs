@@ -1,24 +1,69 @@
+use std::collections::{HashMap, HashSet};
+
 use macroquad::{
     color::Color,
-    math::Vec2,
+    math::{vec2, Vec2},
     miniquad::window::screen_size,
-    shapes::{draw_ellipse, draw_line},
+    shapes::{draw_circle, draw_circle_lines, draw_line, draw_triangle},
     window::clear_background,
 };
 
-use crate::{physics_system::PhysicsSystem, point::Point};
+use crate::{
+    camera::Camera,
+    constraint::{Constraint, ConstraintKind},
+    force_field::{ForceField, ForceFieldKind},
+    obstacle::{Obstacle, ObstacleShape},
+    physics_system::PhysicsSystem,
+    point::Point,
+    simulator::{SimulationBoundingBox, WallKind},
+};
 
 const POINT_BORDER_SIZE: f32 = 0.2;
+const MIN_LINE_STIFFNESS_SCALE: f32 = 0.4;
+const MAX_LINE_STIFFNESS_SCALE: f32 = 2.5;
+const ROPE_DASH_LENGTH: f32 = 0.01;
+const ROPE_LINE_SCALE: f32 = 0.6;
+const STRUT_LINE_SPACING: f32 = 1.5;
+const ROD_LINE_SCALE: f32 = 1.5;
+const HIGHLIGHT_LINE_SCALE: f32 = 4.0;
+const SELECTION_RING_SCALE: f32 = 1.4;
+/// Force field arrows are drawn halfway to the edge and this long relative to the radius
+const FORCE_FIELD_ARROW_LENGTH: f32 = 0.25;
+const FORCE_FIELD_ARROW_HEAD: f32 = 0.3;
+const FORCE_FIELD_CENTER_SCALE: f32 = 2.0;
+/// Open, deleting and periodic sides are drawn fainter than solid ones
+const NON_SOLID_WALL_ALPHA: f32 = 0.35;
 
 pub struct DrawParams {
     pub bg_color: Color,
-    pub point_size: f32,
     pub line_size: f32,
     pub point_color: Color,
     pub point_border_color: Color,
     pub line_color: Color,
     pub stressed_line_color: Color,
     pub static_point_color: Color,
+    pub rod_line_color: Color,
+    pub bounding_box_color: Color,
+    pub hover_color: Color,
+    pub selection_color: Color,
+    pub obstacle_color: Color,
+    pub obstacle_fill_color: Color,
+    pub force_field_color: Color,
+    pub positive_charge_color: Color,
+    pub negative_charge_color: Color,
+}
+
+/// Locations of the previous step, blended with the current ones by `alpha`
+pub struct Interpolation<'a> {
+    pub previous_locations: &'a HashMap<u64, Vec2>,
+    pub alpha: f32,
+}
+
+/// Constraints are identified by the ids of their two points
+pub struct Highlights<'a> {
+    pub hovered_constraint: Option<(u64, u64)>,
+    pub selected_constraint: Option<(u64, u64)>,
+    pub selected_points: &'a HashSet<u64>,
 }
 
 pub struct Renderer {
@@ -29,39 +74,68 @@ impl Renderer {
         Renderer { draw_params }
     }
 
-    fn draw_point(&self, point: &Point, screen_size: (f32, f32)) {
+    /// Tint of a charged point by the sign of its charge
+    fn get_charge_color(&self, charge: f32) -> Option<Color> {
+        if charge > 0.0 {
+            Some(self.draw_params.positive_charge_color)
+        } else if charge < 0.0 {
+            Some(self.draw_params.negative_charge_color)
+        } else {
+            None
+        }
+    }
+
+    /// Static points keep their color inside and show their charge on the border only
+    fn draw_point(&self, point: &Point, location: Vec2, radius: f32) {
+        let charge_color = self.get_charge_color(point.charge);
         let inner_color = if point.is_static {
             self.draw_params.static_point_color
         } else {
-            self.draw_params.point_color
+            charge_color.unwrap_or(self.draw_params.point_color)
         };
 
-        draw_ellipse(
-            point.location.x * screen_size.0,
-            point.location.y * screen_size.1,
-            self.draw_params.point_size * screen_size.0,
-            self.draw_params.point_size * screen_size.1,
-            0.0,
-            self.draw_params.point_border_color,
+        draw_circle(
+            location.x,
+            location.y,
+            radius,
+            charge_color.unwrap_or(self.draw_params.point_border_color),
         );
-        draw_ellipse(
-            point.location.x * screen_size.0,
-            point.location.y * screen_size.1,
-            self.draw_params.point_size * screen_size.0 * (1.0 - POINT_BORDER_SIZE),
-            self.draw_params.point_size * screen_size.1 * (1.0 - POINT_BORDER_SIZE),
-            0.0,
+        draw_circle(
+            location.x,
+            location.y,
+            radius * (1.0 - POINT_BORDER_SIZE),
             inner_color,
         );
     }
 
+    fn draw_dashed_line(from: Vec2, to: Vec2, dash_length: f32, thickness: f32, color: Color) {
+        let length = from.distance(to);
+        let dashes = (length / dash_length).ceil() as usize;
+        for dash in (0..dashes).step_by(2) {
+            let dash_from = from.lerp(to, dash as f32 / dashes as f32);
+            let dash_to = from.lerp(to, ((dash + 1) as f32 / dashes as f32).min(1.0));
+            draw_line(
+                dash_from.x,
+                dash_from.y,
+                dash_to.x,
+                dash_to.y,
+                thickness,
+                color,
+            );
+        }
+    }
+
+    /// `from` and `to` are in screen pixels, `scale` is the camera's pixels per world unit
     fn draw_constraint(
         &self,
+        kind: ConstraintKind,
         constraint_length: f32,
+        relative_stiffness: f32,
         from: Vec2,
         to: Vec2,
-        screen_size: (f32, f32),
+        scale: f32,
     ) {
-        let points_distance = from.distance(to);
+        let points_distance = from.distance(to) / scale;
         let constraint_stress =
             ((points_distance - constraint_length).abs() / constraint_length).min(1.0);
 
@@ -75,37 +149,277 @@ impl Renderer {
             self.draw_params.line_color.a * (1.0 - constraint_stress)
                 + self.draw_params.stressed_line_color.a * constraint_stress,
         );
+        let thickness = self.draw_params.line_size
+            * relative_stiffness
+                .sqrt()
+                .clamp(MIN_LINE_STIFFNESS_SCALE, MAX_LINE_STIFFNESS_SCALE)
+            * scale;
 
+        match kind {
+            ConstraintKind::Spring => draw_line(from.x, from.y, to.x, to.y, thickness, line_color),
+            ConstraintKind::Rope => Self::draw_dashed_line(
+                from,
+                to,
+                ROPE_DASH_LENGTH * scale,
+                thickness * ROPE_LINE_SCALE,
+                line_color,
+            ),
+            ConstraintKind::Strut => {
+                let offset =
+                    (to - from).normalize_or_zero().perp() * thickness * STRUT_LINE_SPACING;
+                for side in [offset, -offset] {
+                    draw_line(
+                        from.x + side.x,
+                        from.y + side.y,
+                        to.x + side.x,
+                        to.y + side.y,
+                        thickness,
+                        line_color,
+                    );
+                }
+            }
+            ConstraintKind::Rod => draw_line(
+                from.x,
+                from.y,
+                to.x,
+                to.y,
+                self.draw_params.line_size * ROD_LINE_SCALE * scale,
+                self.draw_params.rod_line_color,
+            ),
+        }
+    }
+
+    fn draw_constraint_highlight(&self, from: Vec2, to: Vec2, color: Color, scale: f32) {
         draw_line(
-            from.x * screen_size.0,
-            from.y * screen_size.1,
-            to.x * screen_size.0,
-            to.y * screen_size.1,
-            self.draw_params.line_size * screen_size.0.max(screen_size.1),
-            line_color,
+            from.x,
+            from.y,
+            to.x,
+            to.y,
+            self.draw_params.line_size * HIGHLIGHT_LINE_SCALE * scale,
+            color,
+        );
+    }
+
+    fn get_constraint_highlight_color(
+        &self,
+        highlights: &Highlights,
+        constraint: &Constraint,
+    ) -> Option<Color> {
+        let is_constraint = |pair: Option<(u64, u64)>| {
+            pair.is_some_and(|(point1, point2)| {
+                (constraint.get_point1(), constraint.get_point2()) == (point1, point2)
+                    || (constraint.get_point1(), constraint.get_point2()) == (point2, point1)
+            })
+        };
+
+        if is_constraint(highlights.selected_constraint) {
+            Some(self.draw_params.selection_color)
+        } else if is_constraint(highlights.hovered_constraint) {
+            Some(self.draw_params.hover_color)
+        } else {
+            None
+        }
+    }
+
+    /// Heightmaps are filled down to the bottom of the screen, other shapes are outlined
+    fn draw_obstacle(&self, obstacle: &Obstacle, camera: &Camera, screen_size: (f32, f32)) {
+        if let ObstacleShape::Heightmap { .. } = obstacle.shape {
+            let screen_bottom = camera
+                .screen_to_world(vec2(0.0, screen_size.1), screen_size)
+                .y;
+            for (from, to) in obstacle.shape.get_edges() {
+                let bottom = screen_bottom.max(from.y).max(to.y);
+                let top_left = camera.world_to_screen(from, screen_size);
+                let top_right = camera.world_to_screen(to, screen_size);
+                let bottom_left = camera.world_to_screen(vec2(from.x, bottom), screen_size);
+                let bottom_right = camera.world_to_screen(vec2(to.x, bottom), screen_size);
+                draw_triangle(
+                    top_left,
+                    top_right,
+                    bottom_right,
+                    self.draw_params.obstacle_fill_color,
+                );
+                draw_triangle(
+                    top_left,
+                    bottom_right,
+                    bottom_left,
+                    self.draw_params.obstacle_fill_color,
+                );
+            }
+        }
+
+        let thickness = self.draw_params.line_size * camera.get_scale(screen_size);
+        for (from, to) in obstacle.shape.get_edges() {
+            let from = camera.world_to_screen(from, screen_size);
+            let to = camera.world_to_screen(to, screen_size);
+            draw_line(
+                from.x,
+                from.y,
+                to.x,
+                to.y,
+                thickness,
+                self.draw_params.obstacle_color,
+            );
+        }
+    }
+
+    /// `from` and `to` are in screen pixels
+    fn draw_arrow(from: Vec2, to: Vec2, thickness: f32, color: Color) {
+        draw_line(from.x, from.y, to.x, to.y, thickness, color);
+        let back = (from - to) * FORCE_FIELD_ARROW_HEAD;
+        for head in [to + back + back.perp() / 2.0, to + back - back.perp() / 2.0] {
+            draw_line(to.x, to.y, head.x, head.y, thickness, color);
+        }
+    }
+
+    /// Outline of the region with arrows showing which way points are pushed
+    pub fn draw_force_field(
+        &self,
+        force_field: &ForceField,
+        camera: &Camera,
+        screen_size: (f32, f32),
+    ) {
+        let color = self.draw_params.force_field_color;
+        let thickness = self.draw_params.line_size * camera.get_scale(screen_size);
+        let center = camera.world_to_screen(force_field.center, screen_size);
+        let radius = camera.world_to_screen_length(force_field.radius, screen_size);
+        draw_circle_lines(center.x, center.y, radius, thickness, color);
+        draw_circle(
+            center.x,
+            center.y,
+            thickness * FORCE_FIELD_CENTER_SCALE,
+            color,
         );
+
+        let arrow_length = radius * FORCE_FIELD_ARROW_LENGTH;
+        let sign = force_field.strength.signum();
+        let arrows: Vec<_> = match force_field.kind {
+            ForceFieldKind::Radial { .. } => [vec2(1.0, 0.0), vec2(0.0, 1.0)]
+                .into_iter()
+                .flat_map(|axis| [axis, -axis])
+                .map(|direction| {
+                    let middle = center + direction * radius / 2.0;
+                    (middle, middle - direction * arrow_length * sign)
+                })
+                .collect(),
+            ForceFieldKind::Wind { direction } => {
+                let half = direction * radius / 2.0 * sign;
+                vec![(center - half, center + half)]
+            }
+            ForceFieldKind::Vortex => [vec2(1.0, 0.0), vec2(0.0, 1.0)]
+                .into_iter()
+                .flat_map(|axis| [axis, -axis])
+                .map(|direction| {
+                    let middle = center + direction * radius / 2.0;
+                    (middle, middle + direction.perp() * arrow_length * sign)
+                })
+                .collect(),
+        };
+        for (from, to) in arrows {
+            Self::draw_arrow(from, to, thickness, color);
+        }
     }
 
-    pub fn draw(&self, physics_system: &PhysicsSystem) {
+    fn draw_bounding_box(
+        &self,
+        bounding_box: &SimulationBoundingBox,
+        camera: &Camera,
+        screen_size: (f32, f32),
+    ) {
+        let top_left =
+            camera.world_to_screen(vec2(bounding_box.min_x, bounding_box.min_y), screen_size);
+        let bottom_right =
+            camera.world_to_screen(vec2(bounding_box.max_x, bounding_box.max_y), screen_size);
+        let top_right = vec2(bottom_right.x, top_left.y);
+        let bottom_left = vec2(top_left.x, bottom_right.y);
+        let thickness = self.draw_params.line_size * camera.get_scale(screen_size);
+
+        for (wall, from, to) in [
+            (&bounding_box.left, top_left, bottom_left),
+            (&bounding_box.right, top_right, bottom_right),
+            (&bounding_box.top, top_left, top_right),
+            (&bounding_box.bottom, bottom_left, bottom_right),
+        ] {
+            let mut color = self.draw_params.bounding_box_color;
+            if wall.kind != WallKind::Solid {
+                color.a *= NON_SOLID_WALL_ALPHA;
+            }
+            draw_line(from.x, from.y, to.x, to.y, thickness, color);
+        }
+    }
+
+    fn interpolate_location(
+        id: u64,
+        point: &Point,
+        previous_locations: &HashMap<u64, Vec2>,
+        alpha: f32,
+    ) -> Vec2 {
+        match previous_locations.get(&id) {
+            Some(previous) if !point.is_static => previous.lerp(point.location, alpha),
+            _ => point.location,
+        }
+    }
+
+    /// Draws the system between the previous and current step, `alpha` being the fraction of a step.
+    /// Constraint lines are thickened relative to `default_stiffness`
+    pub fn draw(
+        &self,
+        physics_system: &PhysicsSystem,
+        bounding_box: &SimulationBoundingBox,
+        camera: &Camera,
+        default_stiffness: f32,
+        interpolation: &Interpolation,
+        highlights: &Highlights,
+    ) {
         clear_background(self.draw_params.bg_color);
         let screen_size = screen_size();
+        let scale = camera.get_scale(screen_size);
         let points_ids = physics_system.get_points_ids();
+        let location_of = |id: u64| {
+            let location = Self::interpolate_location(
+                id,
+                points_ids.get(&id).unwrap(),
+                interpolation.previous_locations,
+                interpolation.alpha,
+            );
+            camera.world_to_screen(location, screen_size)
+        };
+
+        self.draw_bounding_box(bounding_box, camera, screen_size);
+        for obstacle in physics_system.get_obstacles() {
+            self.draw_obstacle(obstacle, camera, screen_size);
+        }
+        for force_field in physics_system.get_force_fields() {
+            self.draw_force_field(force_field, camera, screen_size);
+        }
 
         for constraint in physics_system.get_constraints() {
+            let from = location_of(constraint.get_point1());
+            let to = location_of(constraint.get_point2());
+            if let Some(color) = self.get_constraint_highlight_color(highlights, constraint) {
+                self.draw_constraint_highlight(from, to, color, scale);
+            }
             self.draw_constraint(
+                constraint.get_kind(),
                 constraint.get_distance(),
-                points_ids.get(&constraint.get_point1()).unwrap().location,
-                points_ids.get(&constraint.get_point2()).unwrap().location,
-                screen_size,
+                constraint.get_stiffness_or(default_stiffness) / default_stiffness,
+                from,
+                to,
+                scale,
             );
         }
 
-        for point in physics_system.get_points_ids().values() {
-            self.draw_point(point, screen_size);
+        for (id, point) in points_ids {
+            let location = location_of(*id);
+            if highlights.selected_points.contains(id) {
+                draw_circle(
+                    location.x,
+                    location.y,
+                    point.radius * scale * SELECTION_RING_SCALE,
+                    self.draw_params.selection_color,
+                );
+            }
+            self.draw_point(point, location, point.radius * scale);
         }
     }
-
-    pub fn get_draw_params(&self) -> &DrawParams {
-        &self.draw_params
-    }
 }
@@ -0,0 +1,833 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    fs::{read_to_string, write},
+};
+
+use glam::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constraint::{BreakThreshold, Constraint, ConstraintKind},
+    force_field::{Falloff, ForceField, ForceFieldKind},
+    obstacle::{Obstacle, ObstacleShape},
+    physics_system::PhysicsSystem,
+    point::{is_valid_contact_material, CollisionFilter, Point},
+    simulation_config::SimulationConfig,
+    simulator::SimulationParams,
+};
+
+#[derive(Debug)]
+pub enum SceneError {
+    InvalidSimulationConfig(&'static str),
+    DuplicatePointId(u64),
+    InvalidPointMass(u64),
+    InvalidPointRadius(u64),
+    InvalidPointLocation(u64),
+    InvalidPointVelocity(u64),
+    IdCounterTooLow { id_counter: u64, point_id: u64 },
+    MissingConstraintPoint { constraint: usize, point_id: u64 },
+    SelfConstraint { constraint: usize },
+    DuplicateConstraint { constraint: usize },
+    InvalidConstraintDistance { constraint: usize },
+    InvalidConstraintStiffness { constraint: usize },
+    InvalidConstraintDamping { constraint: usize },
+    InvalidBreakThreshold { constraint: usize },
+    RodForceThreshold { constraint: usize },
+    InvalidObstacle { obstacle: usize },
+    InvalidObstacleMaterial { obstacle: usize },
+    InvalidForceField { force_field: usize },
+}
+impl Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::InvalidSimulationConfig(field) => {
+                write!(f, "simulation config has an invalid '{field}'")
+            }
+            SceneError::DuplicatePointId(id) => write!(f, "point id {id} is used more than once"),
+            SceneError::InvalidPointMass(id) => {
+                write!(f, "point {id} has a non-positive or non-finite mass")
+            }
+            SceneError::InvalidPointRadius(id) => {
+                write!(f, "point {id} has a non-positive or non-finite radius")
+            }
+            SceneError::InvalidPointLocation(id) => {
+                write!(f, "point {id} has a non-finite location")
+            }
+            SceneError::InvalidPointVelocity(id) => {
+                write!(f, "point {id} has a non-finite velocity")
+            }
+            SceneError::IdCounterTooLow {
+                id_counter,
+                point_id,
+            } => write!(
+                f,
+                "id counter {id_counter} is not above the point id {point_id}"
+            ),
+            SceneError::MissingConstraintPoint {
+                constraint,
+                point_id,
+            } => write!(
+                f,
+                "constraint {constraint} references the missing point {point_id}"
+            ),
+            SceneError::SelfConstraint { constraint } => {
+                write!(f, "constraint {constraint} connects a point to itself")
+            }
+            SceneError::DuplicateConstraint { constraint } => {
+                write!(
+                    f,
+                    "constraint {constraint} connects points that are already connected"
+                )
+            }
+            SceneError::InvalidConstraintDistance { constraint } => {
+                write!(
+                    f,
+                    "constraint {constraint} has a non-positive or non-finite distance"
+                )
+            }
+            SceneError::InvalidConstraintStiffness { constraint } => {
+                write!(
+                    f,
+                    "constraint {constraint} has a non-positive or non-finite stiffness"
+                )
+            }
+            SceneError::InvalidConstraintDamping { constraint } => {
+                write!(
+                    f,
+                    "constraint {constraint} has a negative or non-finite damping"
+                )
+            }
+            SceneError::InvalidBreakThreshold { constraint } => {
+                write!(
+                    f,
+                    "constraint {constraint} has a non-positive or non-finite break threshold"
+                )
+            }
+            SceneError::RodForceThreshold { constraint } => {
+                write!(
+                    f,
+                    "constraint {constraint} is a rod with a force break threshold"
+                )
+            }
+            SceneError::InvalidObstacle { obstacle } => {
+                write!(
+                    f,
+                    "obstacle {obstacle} has too few or non-finite vertices or a bad spacing"
+                )
+            }
+            SceneError::InvalidObstacleMaterial { obstacle } => {
+                write!(
+                    f,
+                    "obstacle {obstacle} has a restitution outside 0 to 1 or a bad friction"
+                )
+            }
+            SceneError::InvalidForceField { force_field } => {
+                write!(
+                    f,
+                    "force field {force_field} has an invalid center, radius, strength or direction"
+                )
+            }
+        }
+    }
+}
+impl Error for SceneError {}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScenePoint {
+    id: u64,
+    location: [f32; 2],
+    velocity: [f32; 2],
+    is_static: bool,
+    radius: f32,
+    mass: f32,
+    #[serde(default)]
+    charge: f32,
+    #[serde(default)]
+    collision_filter: CollisionFilter,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SceneConstraint {
+    point1: u64,
+    point2: u64,
+    distance: f32,
+    kind: ConstraintKind,
+    stiffness: Option<f32>,
+    damping: Option<f32>,
+    break_threshold: Option<BreakThreshold>,
+    #[serde(default)]
+    is_colliding: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum SceneObstacleShape {
+    Segment {
+        from: [f32; 2],
+        to: [f32; 2],
+    },
+    Polygon {
+        vertices: Vec<[f32; 2]>,
+    },
+    Heightmap {
+        min_x: f32,
+        spacing: f32,
+        heights: Vec<f32>,
+    },
+}
+impl SceneObstacleShape {
+    fn is_valid(&self) -> bool {
+        match self {
+            SceneObstacleShape::Segment { from, to } => {
+                Vec2::from(*from).is_finite() && Vec2::from(*to).is_finite()
+            }
+            SceneObstacleShape::Polygon { vertices } => {
+                vertices.len() >= 3
+                    && vertices
+                        .iter()
+                        .all(|vertex| Vec2::from(*vertex).is_finite())
+            }
+            SceneObstacleShape::Heightmap {
+                min_x,
+                spacing,
+                heights,
+            } => {
+                min_x.is_finite()
+                    && *spacing > 0.0
+                    && spacing.is_finite()
+                    && heights.len() >= 2
+                    && heights.iter().all(|height| height.is_finite())
+            }
+        }
+    }
+}
+impl From<&ObstacleShape> for SceneObstacleShape {
+    fn from(value: &ObstacleShape) -> Self {
+        match value {
+            ObstacleShape::Segment { from, to } => SceneObstacleShape::Segment {
+                from: from.to_array(),
+                to: to.to_array(),
+            },
+            ObstacleShape::Polygon { vertices } => SceneObstacleShape::Polygon {
+                vertices: vertices.iter().map(|vertex| vertex.to_array()).collect(),
+            },
+            ObstacleShape::Heightmap {
+                min_x,
+                spacing,
+                heights,
+            } => SceneObstacleShape::Heightmap {
+                min_x: *min_x,
+                spacing: *spacing,
+                heights: heights.clone(),
+            },
+        }
+    }
+}
+impl From<SceneObstacleShape> for ObstacleShape {
+    fn from(value: SceneObstacleShape) -> Self {
+        match value {
+            SceneObstacleShape::Segment { from, to } => ObstacleShape::Segment {
+                from: from.into(),
+                to: to.into(),
+            },
+            SceneObstacleShape::Polygon { vertices } => ObstacleShape::Polygon {
+                vertices: vertices.into_iter().map(Into::into).collect(),
+            },
+            SceneObstacleShape::Heightmap {
+                min_x,
+                spacing,
+                heights,
+            } => ObstacleShape::Heightmap {
+                min_x,
+                spacing,
+                heights,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SceneObstacle {
+    shape: SceneObstacleShape,
+    restitution: f32,
+    friction: f32,
+}
+impl SceneObstacle {
+    fn has_valid_material(&self) -> bool {
+        is_valid_contact_material(self.restitution, self.friction)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum SceneForceFieldKind {
+    Radial { falloff: Falloff },
+    Wind { direction: [f32; 2] },
+    Vortex,
+}
+impl SceneForceFieldKind {
+    /// Unit direction of a wind, `None` for other kinds or a direction too short to normalize
+    fn get_wind_direction(&self) -> Option<Vec2> {
+        match self {
+            SceneForceFieldKind::Wind { direction } => Vec2::from(*direction).try_normalize(),
+            _ => None,
+        }
+    }
+}
+impl From<ForceFieldKind> for SceneForceFieldKind {
+    fn from(value: ForceFieldKind) -> Self {
+        match value {
+            ForceFieldKind::Radial { falloff } => SceneForceFieldKind::Radial { falloff },
+            ForceFieldKind::Wind { direction } => SceneForceFieldKind::Wind {
+                direction: direction.to_array(),
+            },
+            ForceFieldKind::Vortex => SceneForceFieldKind::Vortex,
+        }
+    }
+}
+impl From<SceneForceFieldKind> for ForceFieldKind {
+    fn from(value: SceneForceFieldKind) -> Self {
+        match value {
+            SceneForceFieldKind::Radial { falloff } => ForceFieldKind::Radial { falloff },
+            SceneForceFieldKind::Wind { .. } => ForceFieldKind::Wind {
+                direction: value
+                    .get_wind_direction()
+                    .expect("Wind direction should be validated"),
+            },
+            SceneForceFieldKind::Vortex => ForceFieldKind::Vortex,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SceneForceField {
+    kind: SceneForceFieldKind,
+    center: [f32; 2],
+    radius: f32,
+    strength: f32,
+}
+impl SceneForceField {
+    fn is_valid(&self) -> bool {
+        let has_direction = match self.kind {
+            SceneForceFieldKind::Wind { .. } => self.kind.get_wind_direction().is_some(),
+            _ => true,
+        };
+
+        Vec2::from(self.center).is_finite()
+            && self.radius > 0.0
+            && self.radius.is_finite()
+            && self.strength.is_finite()
+            && has_direction
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Scene {
+    id_counter: u64,
+    simulation_config: SimulationConfig,
+    points: Vec<ScenePoint>,
+    constraints: Vec<SceneConstraint>,
+    #[serde(default)]
+    obstacles: Vec<SceneObstacle>,
+    #[serde(default)]
+    force_fields: Vec<SceneForceField>,
+}
+impl Scene {
+    pub fn new(physics_system: &PhysicsSystem, params: &SimulationParams) -> Self {
+        let points = physics_system
+            .get_sorted_ids()
+            .iter()
+            .map(|id| {
+                let point = &physics_system.get_points_ids()[id];
+                ScenePoint {
+                    id: *id,
+                    location: point.location.to_array(),
+                    velocity: point.velocity.to_array(),
+                    is_static: point.is_static,
+                    radius: point.radius,
+                    mass: point.mass,
+                    charge: point.charge,
+                    collision_filter: point.collision_filter,
+                }
+            })
+            .collect();
+
+        let constraints = physics_system
+            .get_constraints()
+            .iter()
+            .map(|c| SceneConstraint {
+                point1: c.get_point1(),
+                point2: c.get_point2(),
+                distance: c.get_distance(),
+                kind: c.get_kind(),
+                stiffness: c.get_stiffness(),
+                damping: c.get_damping(),
+                break_threshold: c.get_break_threshold(),
+                is_colliding: c.is_colliding(),
+            })
+            .collect();
+
+        let obstacles = physics_system
+            .get_obstacles()
+            .iter()
+            .map(|obstacle| SceneObstacle {
+                shape: (&obstacle.shape).into(),
+                restitution: obstacle.restitution,
+                friction: obstacle.friction,
+            })
+            .collect();
+
+        let force_fields = physics_system
+            .get_force_fields()
+            .iter()
+            .map(|force_field| SceneForceField {
+                kind: force_field.kind.into(),
+                center: force_field.center.to_array(),
+                radius: force_field.radius,
+                strength: force_field.strength,
+            })
+            .collect();
+
+        Self {
+            id_counter: physics_system.get_id_counter(),
+            simulation_config: params.into(),
+            points,
+            constraints,
+            obstacles,
+            force_fields,
+        }
+    }
+
+    pub fn load(filepath: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_toml(&read_to_string(filepath)?)
+    }
+
+    pub fn save(&self, filepath: &str) -> Result<(), Box<dyn Error>> {
+        write(filepath, self.to_toml()?)?;
+
+        Ok(())
+    }
+
+    /// Parses and validates a scene from the same text format `save` writes
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn Error>> {
+        let scene: Scene = toml::from_str(text)?;
+        scene.validate()?;
+
+        Ok(scene)
+    }
+
+    pub fn to_toml(&self) -> Result<String, Box<dyn Error>> {
+        Ok(toml::to_string(self)?)
+    }
+
+    fn validate(&self) -> Result<(), SceneError> {
+        if let Some(field) = self.simulation_config.get_invalid_field() {
+            return Err(SceneError::InvalidSimulationConfig(field));
+        }
+
+        let mut ids = HashSet::with_capacity(self.points.len());
+        for point in &self.points {
+            if !ids.insert(point.id) {
+                return Err(SceneError::DuplicatePointId(point.id));
+            }
+            if point.id >= self.id_counter {
+                return Err(SceneError::IdCounterTooLow {
+                    id_counter: self.id_counter,
+                    point_id: point.id,
+                });
+            }
+            if !(point.mass > 0.0 && point.mass.is_finite()) {
+                return Err(SceneError::InvalidPointMass(point.id));
+            }
+            if !(point.radius > 0.0 && point.radius.is_finite()) {
+                return Err(SceneError::InvalidPointRadius(point.id));
+            }
+            if !Vec2::from(point.location).is_finite() {
+                return Err(SceneError::InvalidPointLocation(point.id));
+            }
+            if !Vec2::from(point.velocity).is_finite() {
+                return Err(SceneError::InvalidPointVelocity(point.id));
+            }
+        }
+
+        let mut connected = HashSet::with_capacity(self.constraints.len());
+
+        for (index, constraint) in self.constraints.iter().enumerate() {
+            for point_id in [constraint.point1, constraint.point2] {
+                if !ids.contains(&point_id) {
+                    return Err(SceneError::MissingConstraintPoint {
+                        constraint: index,
+                        point_id,
+                    });
+                }
+            }
+            if constraint.point1 == constraint.point2 {
+                return Err(SceneError::SelfConstraint { constraint: index });
+            }
+            let pair = (
+                constraint.point1.min(constraint.point2),
+                constraint.point1.max(constraint.point2),
+            );
+            if !connected.insert(pair) {
+                return Err(SceneError::DuplicateConstraint { constraint: index });
+            }
+            if !(constraint.distance > 0.0 && constraint.distance.is_finite()) {
+                return Err(SceneError::InvalidConstraintDistance { constraint: index });
+            }
+            if let Some(stiffness) = constraint.stiffness {
+                if !(stiffness > 0.0 && stiffness.is_finite()) {
+                    return Err(SceneError::InvalidConstraintStiffness { constraint: index });
+                }
+            }
+            if let Some(damping) = constraint.damping {
+                if !(damping >= 0.0 && damping.is_finite()) {
+                    return Err(SceneError::InvalidConstraintDamping { constraint: index });
+                }
+            }
+            if let Some(BreakThreshold::Strain(threshold) | BreakThreshold::Force(threshold)) =
+                constraint.break_threshold
+            {
+                if !(threshold > 0.0 && threshold.is_finite()) {
+                    return Err(SceneError::InvalidBreakThreshold { constraint: index });
+                }
+            }
+            if constraint.kind == ConstraintKind::Rod
+                && matches!(constraint.break_threshold, Some(BreakThreshold::Force(_)))
+            {
+                return Err(SceneError::RodForceThreshold { constraint: index });
+            }
+        }
+
+        for (index, obstacle) in self.obstacles.iter().enumerate() {
+            if !obstacle.shape.is_valid() {
+                return Err(SceneError::InvalidObstacle { obstacle: index });
+            }
+            if !obstacle.has_valid_material() {
+                return Err(SceneError::InvalidObstacleMaterial { obstacle: index });
+            }
+        }
+
+        for (index, force_field) in self.force_fields.iter().enumerate() {
+            if !force_field.is_valid() {
+                return Err(SceneError::InvalidForceField { force_field: index });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Splits a validated scene into the system it describes and its simulation parameters
+    pub fn into_parts(self) -> (PhysicsSystem, SimulationParams) {
+        let points: HashMap<_, _> = self
+            .points
+            .into_iter()
+            .map(|point| {
+                let mut new_point = Point::new(
+                    vec2(point.location[0], point.location[1]),
+                    vec2(point.velocity[0], point.velocity[1]),
+                    point.is_static,
+                    point.radius,
+                    point.mass,
+                );
+                new_point.charge = point.charge;
+                new_point.collision_filter = point.collision_filter;
+
+                (point.id, new_point)
+            })
+            .collect();
+
+        let constraints = self
+            .constraints
+            .into_iter()
+            .map(|c| {
+                let mut constraint = Constraint::new(
+                    c.point1,
+                    c.point2,
+                    c.distance,
+                    c.kind,
+                    c.stiffness,
+                    c.damping,
+                    c.break_threshold,
+                );
+                constraint.set_colliding(c.is_colliding);
+                constraint
+            })
+            .collect();
+
+        let mut physics_system = PhysicsSystem::from_parts(self.id_counter, points, constraints);
+        for obstacle in self.obstacles {
+            physics_system.add_obstacle(Obstacle::new(
+                obstacle.shape.into(),
+                obstacle.restitution,
+                obstacle.friction,
+            ));
+        }
+        for force_field in self.force_fields {
+            physics_system.add_force_field(ForceField::new(
+                force_field.kind.into(),
+                force_field.center.into(),
+                force_field.radius,
+                force_field.strength,
+            ));
+        }
+
+        (physics_system, self.simulation_config.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constraint::ConstraintKind, simulation_config::SimulationFileConfig, simulator::Simulator,
+    };
+
+    use super::*;
+
+    const CONFIG_FIELDS: [&str; 12] = [
+        "air_resistence",
+        "gravity",
+        "point_size",
+        "spring_coeff",
+        "damping",
+        "collision_force",
+        "n_body.gravitational_constant",
+        "n_body.softening",
+        "n_body.theta",
+        "coulomb.coulomb_constant",
+        "coulomb.cutoff",
+        "coulomb.softening",
+    ];
+
+    fn get_simulator() -> Simulator {
+        toml::from_str::<SimulationFileConfig>(include_str!("../config.toml"))
+            .expect("Config should parse")
+            .into()
+    }
+
+    /// Points 0 and 2 with a gap where point 1 was removed, connected by a spring, next to a
+    /// triangle and inside a wind
+    fn get_physics_system() -> PhysicsSystem {
+        let mut physics_system = PhysicsSystem::new();
+        for i in 0..3 {
+            physics_system.add_point(Point::new(
+                vec2(0.2 + 0.2 * i as f32, 0.5),
+                vec2(0.1, 0.0),
+                i == 0,
+                0.01,
+                1.0 + i as f32,
+            ));
+        }
+        physics_system.remove_point(1);
+        physics_system.add_constraint(Constraint::new(
+            0,
+            2,
+            0.4,
+            ConstraintKind::Spring,
+            Some(300.0),
+            None,
+            Some(BreakThreshold::Strain(0.5)),
+        ));
+        physics_system.add_obstacle(Obstacle::new(
+            ObstacleShape::Polygon {
+                vertices: vec![vec2(0.1, 0.8), vec2(0.3, 0.8), vec2(0.2, 0.9)],
+            },
+            0.3,
+            0.4,
+        ));
+        physics_system.add_force_field(ForceField::new(
+            ForceFieldKind::Wind {
+                direction: vec2(1.0, 0.0),
+            },
+            vec2(0.5, 0.5),
+            0.3,
+            0.1,
+        ));
+
+        physics_system
+    }
+
+    fn get_scene() -> Scene {
+        Scene::new(&get_physics_system(), get_simulator().get_params())
+    }
+
+    /// Error of a valid scene after `edit`
+    fn get_error(edit: impl FnOnce(&mut Scene)) -> SceneError {
+        let mut scene = get_scene();
+        scene.validate().expect("Unedited scene should be valid");
+        edit(&mut scene);
+
+        scene
+            .validate()
+            .expect_err("Edited scene should be invalid")
+    }
+
+    /// Sets a value of the simulation config by its dotted name
+    fn set_config_value(scene: &mut Scene, name: &str, value: f32) {
+        let mut config = toml::Value::try_from(&scene.simulation_config).unwrap();
+        let mut table = &mut config;
+        let mut keys = name.split('.').peekable();
+        while let Some(key) = keys.next() {
+            if keys.peek().is_none() {
+                table[key] = toml::Value::Float(value.into());
+            } else {
+                table = &mut table[key];
+            }
+        }
+        scene.simulation_config = config.try_into().unwrap();
+    }
+
+    #[test]
+    fn round_trip_keeps_ids_and_id_counter() {
+        let physics_system = get_physics_system();
+        let text = Scene::new(&physics_system, get_simulator().get_params())
+            .to_toml()
+            .unwrap();
+        let (loaded, _params) = Scene::from_toml(&text).unwrap().into_parts();
+
+        assert_eq!(loaded.get_id_counter(), 3);
+        assert_eq!(
+            loaded.get_points_ids().len(),
+            physics_system.get_points_ids().len()
+        );
+        for (id, point) in physics_system.get_points_ids() {
+            assert_eq!(loaded.get_point(*id), Some(point));
+        }
+        assert_eq!(loaded.get_constraints(), physics_system.get_constraints());
+        assert_eq!(loaded.get_obstacles().len(), 1);
+        assert_eq!(loaded.get_force_fields().len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_simulation_config() {
+        for field in CONFIG_FIELDS {
+            for value in [f32::NAN, f32::INFINITY, -1.0] {
+                let error = get_error(|scene| set_config_value(scene, field, value));
+                assert!(
+                    matches!(error, SceneError::InvalidSimulationConfig(name) if name == field),
+                    "{field} = {value} gave {error}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_points() {
+        assert!(matches!(
+            get_error(|scene| scene.points[1].id = 0),
+            SceneError::DuplicatePointId(0)
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.id_counter = 2),
+            SceneError::IdCounterTooLow {
+                id_counter: 2,
+                point_id: 2
+            }
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.points[0].mass = 0.0),
+            SceneError::InvalidPointMass(0)
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.points[0].radius = f32::NAN),
+            SceneError::InvalidPointRadius(0)
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.points[1].location[0] = f32::INFINITY),
+            SceneError::InvalidPointLocation(2)
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.points[1].velocity[1] = f32::NAN),
+            SceneError::InvalidPointVelocity(2)
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_constraints() {
+        assert!(matches!(
+            get_error(|scene| scene.constraints[0].point2 = 1),
+            SceneError::MissingConstraintPoint {
+                constraint: 0,
+                point_id: 1
+            }
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.constraints[0].point2 = 0),
+            SceneError::SelfConstraint { constraint: 0 }
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.constraints.push(SceneConstraint {
+                point1: 2,
+                point2: 0,
+                distance: 0.4,
+                kind: ConstraintKind::Rope,
+                stiffness: None,
+                damping: None,
+                break_threshold: None,
+                is_colliding: false,
+            })),
+            SceneError::DuplicateConstraint { constraint: 1 }
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.constraints[0].distance = f32::NAN),
+            SceneError::InvalidConstraintDistance { constraint: 0 }
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.constraints[0].stiffness = Some(-1.0)),
+            SceneError::InvalidConstraintStiffness { constraint: 0 }
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.constraints[0].damping = Some(f32::INFINITY)),
+            SceneError::InvalidConstraintDamping { constraint: 0 }
+        ));
+        assert!(matches!(
+            get_error(
+                |scene| scene.constraints[0].break_threshold = Some(BreakThreshold::Strain(-1.0))
+            ),
+            SceneError::InvalidBreakThreshold { constraint: 0 }
+        ));
+        assert!(matches!(
+            get_error(|scene| {
+                scene.constraints[0].kind = ConstraintKind::Rod;
+                scene.constraints[0].break_threshold = Some(BreakThreshold::Force(1.0));
+            }),
+            SceneError::RodForceThreshold { constraint: 0 }
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_obstacles_and_force_fields() {
+        assert!(matches!(
+            get_error(
+                |scene| scene.obstacles[0].shape = SceneObstacleShape::Polygon {
+                    vertices: vec![[0.1, 0.8], [0.3, 0.8]],
+                }
+            ),
+            SceneError::InvalidObstacle { obstacle: 0 }
+        ));
+        assert!(matches!(
+            get_error(
+                |scene| scene.obstacles[0].shape = SceneObstacleShape::Segment {
+                    from: [0.1, f32::NAN],
+                    to: [0.3, 0.8],
+                }
+            ),
+            SceneError::InvalidObstacle { obstacle: 0 }
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.obstacles[0].restitution = 1.5),
+            SceneError::InvalidObstacleMaterial { obstacle: 0 }
+        ));
+        assert!(matches!(
+            get_error(|scene| scene.force_fields[0].strength = f32::NAN),
+            SceneError::InvalidForceField { force_field: 0 }
+        ));
+        assert!(matches!(
+            get_error(
+                |scene| scene.force_fields[0].kind = SceneForceFieldKind::Wind {
+                    direction: [1e-40, 0.0],
+                }
+            ),
+            SceneError::InvalidForceField { force_field: 0 }
+        ));
+    }
+}
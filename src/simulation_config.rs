@@ -0,0 +1,224 @@
+use std::{error::Error, fs::read_to_string};
+
+use serde::{Deserialize, Serialize};
+use toml::from_str;
+
+use crate::{
+    coulomb::CoulombParams,
+    n_body::NBodyParams,
+    simulator::{BroadPhase, Integrator, SimulationBoundingBox, SimulationParams, Simulator, Wall},
+};
+
+/// Scenes saved before uniform gravity could be turned off had it on
+fn is_uniform_gravity_default() -> bool {
+    true
+}
+
+/// Scenes saved before XPBD springs and contacts could be turned off had them on
+fn is_solving_default() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    air_resistence: f32,
+    gravity: f32,
+    #[serde(default = "is_uniform_gravity_default")]
+    is_uniform_gravity: bool,
+    #[serde(default)]
+    n_body: NBodyParams,
+    #[serde(default)]
+    coulomb: CoulombParams,
+    point_size: f32,
+    spring_coeff: f32,
+    damping: f32,
+    collision_force: f32,
+    #[serde(default)]
+    skip_connected_collisions: bool,
+    broad_phase: BroadPhase,
+    rod_iterations: usize,
+    integrator: Integrator,
+    solver_iterations: usize,
+    #[serde(default = "is_solving_default")]
+    solve_springs: bool,
+    #[serde(default = "is_solving_default")]
+    solve_contacts: bool,
+    #[serde(default)]
+    parallel_stages: bool,
+}
+impl SimulationConfig {
+    /// Name of the first value that is not finite or out of range, `None` if all are valid
+    pub fn get_invalid_field(&self) -> Option<&'static str> {
+        let is_positive = |value: f32| value > 0.0 && value.is_finite();
+        let is_non_negative = |value: f32| value >= 0.0 && value.is_finite();
+        [
+            ("air_resistence", is_positive(self.air_resistence)),
+            ("gravity", is_positive(self.gravity)),
+            ("point_size", is_positive(self.point_size)),
+            ("spring_coeff", is_positive(self.spring_coeff)),
+            ("damping", is_non_negative(self.damping)),
+            ("collision_force", is_positive(self.collision_force)),
+            (
+                "n_body.gravitational_constant",
+                is_non_negative(self.n_body.gravitational_constant),
+            ),
+            ("n_body.softening", is_non_negative(self.n_body.softening)),
+            ("n_body.theta", is_positive(self.n_body.theta)),
+            (
+                "coulomb.coulomb_constant",
+                is_non_negative(self.coulomb.coulomb_constant),
+            ),
+            ("coulomb.cutoff", is_positive(self.coulomb.cutoff)),
+            ("coulomb.softening", is_non_negative(self.coulomb.softening)),
+        ]
+        .into_iter()
+        .find_map(|(name, is_valid)| (!is_valid).then_some(name))
+    }
+}
+impl From<SimulationConfig> for SimulationParams {
+    fn from(value: SimulationConfig) -> Self {
+        SimulationParams {
+            gravity: value.gravity,
+            is_uniform_gravity: value.is_uniform_gravity,
+            n_body: value.n_body,
+            coulomb: value.coulomb,
+            air_resistence: value.air_resistence,
+            point_size: value.point_size,
+            spring_coeff: value.spring_coeff,
+            damping: value.damping,
+            collision_force: value.collision_force,
+            skip_connected_collisions: value.skip_connected_collisions,
+            broad_phase: value.broad_phase,
+            rod_iterations: value.rod_iterations,
+            integrator: value.integrator,
+            solver_iterations: value.solver_iterations,
+            solve_springs: value.solve_springs,
+            solve_contacts: value.solve_contacts,
+            parallel_stages: value.parallel_stages,
+        }
+    }
+}
+
+impl From<&SimulationParams> for SimulationConfig {
+    fn from(value: &SimulationParams) -> Self {
+        SimulationConfig {
+            gravity: value.gravity,
+            is_uniform_gravity: value.is_uniform_gravity,
+            n_body: value.n_body,
+            coulomb: value.coulomb,
+            air_resistence: value.air_resistence,
+            point_size: value.point_size,
+            spring_coeff: value.spring_coeff,
+            damping: value.damping,
+            collision_force: value.collision_force,
+            skip_connected_collisions: value.skip_connected_collisions,
+            broad_phase: value.broad_phase,
+            rod_iterations: value.rod_iterations,
+            integrator: value.integrator,
+            solver_iterations: value.solver_iterations,
+            solve_springs: value.solve_springs,
+            solve_contacts: value.solve_contacts,
+            parallel_stages: value.parallel_stages,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BoundingBoxConfig {
+    max_x: f32,
+    max_y: f32,
+    min_x: f32,
+    min_y: f32,
+    left: Wall,
+    right: Wall,
+    top: Wall,
+    bottom: Wall,
+}
+impl BoundingBoxConfig {
+    /// Name of the first side with an invalid restitution or friction, `None` if all are valid
+    pub fn get_invalid_wall(&self) -> Option<&'static str> {
+        [
+            ("left", &self.left),
+            ("right", &self.right),
+            ("top", &self.top),
+            ("bottom", &self.bottom),
+        ]
+        .into_iter()
+        .find_map(|(name, wall)| (!wall.has_valid_material()).then_some(name))
+    }
+}
+impl From<BoundingBoxConfig> for SimulationBoundingBox {
+    fn from(value: BoundingBoxConfig) -> Self {
+        SimulationBoundingBox {
+            min_x: value.min_x,
+            max_x: value.max_x,
+            min_y: value.min_y,
+            max_y: value.max_y,
+            left: value.left,
+            right: value.right,
+            top: value.top,
+            bottom: value.bottom,
+        }
+    }
+}
+
+pub fn get_invalid_wall_error(side: &str) -> String {
+    format!("'{side}' wall must have a restitution from 0 to 1 and a finite friction of at least 0")
+}
+
+/// The simulation sections of the config file, ignoring the rendering ones
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimulationFileConfig {
+    bounding_box_config: BoundingBoxConfig,
+    simulation_config: SimulationConfig,
+}
+impl SimulationFileConfig {
+    pub fn load(filepath: &str) -> Result<Self, Box<dyn Error>> {
+        let config: Self = from_str(&read_to_string(filepath)?)?;
+        if let Some(side) = config.bounding_box_config.get_invalid_wall() {
+            return Err(get_invalid_wall_error(side).into());
+        }
+
+        Ok(config)
+    }
+}
+impl From<SimulationFileConfig> for Simulator {
+    fn from(value: SimulationFileConfig) -> Self {
+        Simulator::new(
+            value.simulation_config.into(),
+            value.bounding_box_config.into(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_invalid_wall(side: &str, field: &str, value: f32) -> Option<&'static str> {
+        let mut config: toml::Value = from_str(include_str!("../config.toml")).unwrap();
+        config["bounding_box_config"][side][field] = toml::Value::Float(value.into());
+        let config: SimulationFileConfig = config.try_into().unwrap();
+
+        config.bounding_box_config.get_invalid_wall()
+    }
+
+    #[test]
+    fn rejects_invalid_wall_materials() {
+        assert_eq!(get_invalid_wall("left", "restitution", 0.5), None);
+        assert_eq!(get_invalid_wall("left", "restitution", 1.5), Some("left"));
+        assert_eq!(
+            get_invalid_wall("right", "restitution", -0.1),
+            Some("right")
+        );
+        assert_eq!(
+            get_invalid_wall("top", "restitution", f32::NAN),
+            Some("top")
+        );
+        assert_eq!(get_invalid_wall("bottom", "friction", -1.0), Some("bottom"));
+        assert_eq!(
+            get_invalid_wall("bottom", "friction", f32::INFINITY),
+            Some("bottom")
+        );
+    }
+}
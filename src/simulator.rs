@@ -1,16 +1,105 @@
-use macroquad::math::{vec2, Vec2};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::collections::HashSet;
 
-use crate::{physics_system::PhysicsSystem, point::Point};
+use glam::{vec2, Vec2};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constraint::{BreakThreshold, Constraint, ConstraintKind},
+    coulomb::CoulombParams,
+    force_generator::{self, ForceGenerator},
+    math::project_onto_segment,
+    n_body::NBodyParams,
+    physics_system::PhysicsSystem,
+    point::{is_valid_contact_material, Point},
+    spatial_hash::SpatialHash,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BroadPhase {
+    AllPairs,
+    SpatialHash,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Integrator {
+    /// Semi-implicit Euler on velocities with spring and collision forces
+    SemiImplicitEuler,
+    /// Extended position-based dynamics, constraints and collisions solved as position corrections
+    Xpbd,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SimulationEvent {
+    ConstraintBroken {
+        from: Vec2,
+        to: Vec2,
+    },
+    /// Point went through a periodic side and came back in on the opposite one
+    PointWrapped {
+        id: u64,
+    },
+    /// Point left through a deleting side
+    PointRemoved {
+        id: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WallKind {
+    /// Points bounce off the side
+    Solid,
+    /// Points leave through the side and keep being simulated outside
+    Open,
+    /// Points are removed once they are fully past the side
+    Deleting,
+    /// Points crossing the side reappear on the opposite one. Constraints and collisions do not
+    /// wrap, so it suits loose points more than connected bodies
+    Periodic,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Wall {
+    pub kind: WallKind,
+    /// Fraction of the normal velocity kept after a bounce off a solid side
+    pub restitution: f32,
+    /// Coulomb friction coefficient against the normal impulse of a solid side
+    pub friction: f32,
+}
+impl Wall {
+    pub fn has_valid_material(&self) -> bool {
+        is_valid_contact_material(self.restitution, self.friction)
+    }
+}
 
 pub struct SimulationParams {
     pub gravity: f32,
+    /// Whether `gravity` pulls every point down
+    pub is_uniform_gravity: bool,
+    pub n_body: NBodyParams,
+    pub coulomb: CoulombParams,
     pub air_resistence: f32,
     pub point_size: f32,
     pub spring_coeff: f32,
     pub damping: f32,
     pub collision_force: f32,
-    pub push_from_sides_force: f32,
+    /// Stops points joined by a constraint from colliding with each other
+    pub skip_connected_collisions: bool,
+    pub broad_phase: BroadPhase,
+    pub rod_iterations: usize,
+    pub integrator: Integrator,
+    pub solver_iterations: usize,
+    /// Whether XPBD solves springs, ropes and struts. Semi-implicit Euler uses the springs
+    /// generator instead
+    pub solve_springs: bool,
+    /// Whether XPBD solves contacts between points. Semi-implicit Euler uses the collisions
+    /// generator instead
+    pub solve_contacts: bool,
+    /// Computes the velocity changes of all force generators at once from the same state,
+    /// instead of each one seeing the changes of those before it
+    pub parallel_stages: bool,
 }
 
 pub struct SimulationBoundingBox {
@@ -18,157 +107,1089 @@ pub struct SimulationBoundingBox {
     pub max_x: f32,
     pub min_y: f32,
     pub max_y: f32,
+    /// Side at `min_x`
+    pub left: Wall,
+    /// Side at `max_x`
+    pub right: Wall,
+    /// Side at `min_y`
+    pub top: Wall,
+    /// Side at `max_y`, the floor under positive gravity
+    pub bottom: Wall,
+}
+impl SimulationBoundingBox {
+    /// Each side with its inward normal and its position along that normal
+    fn get_sides(&self) -> [(&Wall, Vec2, f32); 4] {
+        [
+            (&self.left, vec2(1.0, 0.0), self.min_x),
+            (&self.right, vec2(-1.0, 0.0), -self.max_x),
+            (&self.top, vec2(0.0, 1.0), self.min_y),
+            (&self.bottom, vec2(0.0, -1.0), -self.max_y),
+        ]
+    }
+}
+
+/// Decides which pairs of points may collide, before any narrow-phase math is done on them
+pub(crate) struct CollisionPairFilter {
+    /// Constraint ends with the lower id first, empty unless connected points are skipped
+    connected: HashSet<(u64, u64)>,
+}
+impl CollisionPairFilter {
+    pub(crate) fn new(physics_system: &PhysicsSystem, params: &SimulationParams) -> Self {
+        let connected = if params.skip_connected_collisions {
+            physics_system
+                .get_constraints()
+                .iter()
+                .map(|c| Self::get_key(c.get_point1(), c.get_point2()))
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        Self { connected }
+    }
+
+    fn get_key(id1: u64, id2: u64) -> (u64, u64) {
+        (id1.min(id2), id1.max(id2))
+    }
+
+    pub(crate) fn can_collide(&self, id1: u64, point1: &Point, id2: u64, point2: &Point) -> bool {
+        id1 != id2
+            && point1
+                .collision_filter
+                .can_collide(&point2.collision_filter)
+            && !self.connected.contains(&Self::get_key(id1, id2))
+    }
+}
+
+struct Stage {
+    generator: Box<dyn ForceGenerator>,
+    is_enabled: bool,
 }
 
 pub struct Simulator {
     params: SimulationParams,
     bounding_box: SimulationBoundingBox,
+    stages: Vec<Stage>,
 }
 impl Simulator {
+    /// Starts with the stock force generators of `force_generator::get_stock_generators`
     pub fn new(params: SimulationParams, bounding_box: SimulationBoundingBox) -> Self {
-        Self {
+        let mut simulator = Self {
             params,
             bounding_box,
+            stages: vec![],
+        };
+        for generator in force_generator::get_stock_generators() {
+            simulator.add_force_generator(generator);
         }
+
+        simulator
     }
 
-    pub fn calculate_velocity(from: Vec2, to: Vec2, delta: f32) -> Vec2 {
-        (to - from) / delta
+    /// Runs the generator after the existing ones, returns false if one by that name already
+    /// exists
+    pub fn add_force_generator(&mut self, generator: Box<dyn ForceGenerator>) -> bool {
+        self.insert_force_generator(self.stages.len(), generator)
     }
 
-    fn apply_gravity(&self, physics_system: &mut PhysicsSystem, delta: f32) {
-        for point in physics_system.get_points_mut() {
-            if point.is_static {
-                continue;
+    /// Runs the generator at `index` in the order, clamped to the number of generators. Returns
+    /// false if one by that name already exists, as generators are looked up by name
+    pub fn insert_force_generator(
+        &mut self,
+        index: usize,
+        generator: Box<dyn ForceGenerator>,
+    ) -> bool {
+        if self.find_stage_index(generator.get_name()).is_some() {
+            return false;
+        }
+
+        self.stages.insert(
+            index.min(self.stages.len()),
+            Stage {
+                generator,
+                is_enabled: true,
+            },
+        );
+
+        true
+    }
+
+    fn find_stage_index(&self, name: &str) -> Option<usize> {
+        self.stages
+            .iter()
+            .position(|stage| stage.generator.get_name() == name)
+    }
+
+    pub fn remove_force_generator(&mut self, name: &str) -> Option<Box<dyn ForceGenerator>> {
+        let index = self.find_stage_index(name)?;
+
+        Some(self.stages.remove(index).generator)
+    }
+
+    /// Moves the generator to `index` in the order, returns false if there is none by that name
+    pub fn move_force_generator(&mut self, name: &str, index: usize) -> bool {
+        let Some(current) = self.find_stage_index(name) else {
+            return false;
+        };
+
+        let stage = self.stages.remove(current);
+        self.stages.insert(index.min(self.stages.len()), stage);
+
+        true
+    }
+
+    /// Returns false if there is no generator by that name
+    pub fn set_force_generator_enabled(&mut self, name: &str, is_enabled: bool) -> bool {
+        let Some(index) = self.find_stage_index(name) else {
+            return false;
+        };
+        self.stages[index].is_enabled = is_enabled;
+
+        true
+    }
+
+    pub fn is_force_generator_enabled(&self, name: &str) -> bool {
+        self.find_stage_index(name)
+            .is_some_and(|index| self.stages[index].is_enabled)
+    }
+
+    /// Names of the generators in the order they run, with whether they are enabled
+    pub fn get_force_generators(&self) -> Vec<(&str, bool)> {
+        self.stages
+            .iter()
+            .map(|stage| (stage.generator.get_name(), stage.is_enabled))
+            .collect()
+    }
+
+    fn apply_velocity_changes(physics_system: &mut PhysicsSystem, changes: &[(u64, Vec2)]) {
+        for (id, change) in changes {
+            if let Some(point) = physics_system.get_point_mut(*id) {
+                if !point.is_static {
+                    point.velocity += *change;
+                }
+            }
+        }
+    }
+
+    fn apply_force_generators(&self, physics_system: &mut PhysicsSystem, delta: f32) {
+        let enabled_stages = self.stages.iter().filter(|stage| stage.is_enabled);
+        if self.params.parallel_stages {
+            let stages: Vec<_> = enabled_stages.collect();
+            let changes: Vec<_> = stages
+                .par_iter()
+                .map(|stage| {
+                    stage
+                        .generator
+                        .get_velocity_changes(physics_system, &self.params, delta)
+                })
+                .collect();
+            for stage_changes in changes {
+                Self::apply_velocity_changes(physics_system, &stage_changes);
+            }
+        } else {
+            for stage in enabled_stages {
+                let changes =
+                    stage
+                        .generator
+                        .get_velocity_changes(physics_system, &self.params, delta);
+                Self::apply_velocity_changes(physics_system, &changes);
             }
-            point.velocity.y += self.params.gravity * delta;
         }
     }
 
+    pub fn get_bounding_box(&self) -> &SimulationBoundingBox {
+        &self.bounding_box
+    }
+
+    pub fn calculate_velocity(from: Vec2, to: Vec2, delta: f32) -> Vec2 {
+        (to - from) / delta
+    }
+
     fn apply_velocity(&self, physics_system: &mut PhysicsSystem, delta: f32) {
         for point in physics_system.get_points_mut() {
             if point.is_static {
                 point.velocity = vec2(0.0, 0.0);
             } else {
-                point.velocity *= self.params.air_resistence.powf(delta);
                 point.location += point.velocity * delta;
             }
         }
     }
 
-    fn fit_in_screen(&self, physics_system: &mut PhysicsSystem) {
-        for point in physics_system.get_points_mut() {
-            if point.location.x + self.params.point_size > self.bounding_box.max_x {
-                point.location.x = self.bounding_box.max_x - self.params.point_size;
-                point.velocity.x = -self.params.push_from_sides_force;
-            }
+    /// Bounces points off solid sides, wraps them through periodic ones and removes those past
+    /// deleting ones. Static points stay where they were placed, as they do inside obstacles
+    fn apply_walls(&self, physics_system: &mut PhysicsSystem) -> Vec<SimulationEvent> {
+        let sides = self.bounding_box.get_sides();
+        let size = vec2(
+            self.bounding_box.max_x - self.bounding_box.min_x,
+            self.bounding_box.max_y - self.bounding_box.min_y,
+        );
 
-            if point.location.y + self.params.point_size > self.bounding_box.max_y {
-                point.location.y = self.bounding_box.max_y - self.params.point_size;
-                point.velocity.y = 0.0;
+        let mut events = vec![];
+        for (id, point) in physics_system.get_points_ids_mut() {
+            if point.is_static {
+                continue;
             }
+            for (wall, normal, position) in sides {
+                let inside_distance = point.location.dot(normal) - position;
+                match wall.kind {
+                    WallKind::Solid if inside_distance < point.radius => point.resolve_contact(
+                        normal,
+                        point.radius - inside_distance,
+                        wall.restitution,
+                        wall.friction,
+                    ),
+                    WallKind::Deleting if inside_distance < -point.radius => {
+                        events.push(SimulationEvent::PointRemoved { id });
+                        break;
+                    }
+                    WallKind::Periodic if inside_distance < 0.0 => {
+                        point.location += normal * size.dot(normal.abs());
+                        events.push(SimulationEvent::PointWrapped { id });
+                    }
+                    _ => {}
+                }
+            }
+        }
 
-            if point.location.x - self.params.point_size < self.bounding_box.min_x {
-                point.location.x = self.bounding_box.min_x + self.params.point_size;
-                point.velocity.x = self.params.push_from_sides_force;
+        for event in &events {
+            if let SimulationEvent::PointRemoved { id } = event {
+                physics_system.remove_point(*id);
             }
+        }
+
+        events
+    }
+
+    fn apply_obstacle_collisions(physics_system: &mut PhysicsSystem) {
+        let (points, obstacles) = physics_system.get_points_mut_and_obstacles();
+        if obstacles.is_empty() {
+            return;
+        }
 
-            if point.location.y - self.params.point_size < self.bounding_box.min_y {
-                point.location.y = self.bounding_box.min_y + self.params.point_size;
-                point.velocity.y = 0.0;
+        let bounds: Vec<_> = obstacles
+            .iter()
+            .map(|obstacle| obstacle.shape.get_bounds())
+            .collect();
+        points
+            .into_par_iter()
+            .filter(|point| !point.is_static)
+            .for_each(|point| {
+                for (obstacle, (min, max)) in obstacles.iter().zip(&bounds) {
+                    let is_near = point.location.cmpge(*min - point.radius).all()
+                        && point.location.cmple(*max + point.radius).all();
+                    if is_near {
+                        obstacle.collide(point);
+                    }
+                }
+            });
+    }
+
+    /// Points that may touch a colliding constraint they are not an end of, as
+    /// `(point, constraint point1, constraint point2)`
+    fn find_edge_collision_candidates(
+        &self,
+        physics_system: &PhysicsSystem,
+    ) -> Vec<(u64, u64, u64)> {
+        let colliding: Vec<_> = physics_system
+            .get_constraints()
+            .iter()
+            .filter(|constraint| constraint.is_colliding())
+            .map(|constraint| (constraint.get_point1(), constraint.get_point2()))
+            .collect();
+        if colliding.is_empty() {
+            return vec![];
+        }
+
+        let points = physics_system.get_points_ids();
+        let pair_filter = CollisionPairFilter::new(physics_system, &self.params);
+        let is_candidate = |id: u64, id1: u64, id2: u64| {
+            id != id1
+                && id != id2
+                && pair_filter.can_collide(id, &points[&id], id1, &points[&id1])
+                && pair_filter.can_collide(id, &points[&id], id2, &points[&id2])
+        };
+        let mut candidates = vec![];
+        match self.params.broad_phase {
+            BroadPhase::AllPairs => {
+                for (id1, id2) in colliding {
+                    candidates.extend(
+                        physics_system
+                            .get_sorted_ids()
+                            .iter()
+                            .filter(|id| is_candidate(**id, id1, id2))
+                            .map(|id| (*id, id1, id2)),
+                    );
+                }
+            }
+            BroadPhase::SpatialHash => {
+                let (spatial_hash, max_radius) = Self::build_spatial_hash(physics_system);
+                for (id1, id2) in colliding {
+                    let from = points[&id1].location;
+                    let to = points[&id2].location;
+                    candidates.extend(
+                        spatial_hash
+                            .get_ids_in_box(from.min(to) - max_radius, from.max(to) + max_radius)
+                            .filter(|id| is_candidate(*id, id1, id2))
+                            .map(|id| (id, id1, id2)),
+                    );
+                }
             }
         }
+
+        candidates
     }
 
-    fn apply_point_changes(physics_system: &mut PhysicsSystem, point_changes: &[(u64, u64, Vec2)]) {
-        for (id1, id2, delta_v) in point_changes {
-            let point1 = physics_system.get_point_mut(*id1).expect("Invalid id");
-            point1.velocity += *delta_v;
+    /// Pushes the point off the segment between `id1` and `id2` and removes their approaching
+    /// velocity, splitting the response between the two ends by how close the contact is to each
+    fn solve_edge_contact(physics_system: &mut PhysicsSystem, id: u64, id1: u64, id2: u64) {
+        let point = physics_system.get_point(id).expect("Invalid id");
+        let from = physics_system.get_point(id1).expect("Invalid id");
+        let to = physics_system.get_point(id2).expect("Invalid id");
+
+        let Some(t) = project_onto_segment(point.location, from.location, to.location) else {
+            return;
+        };
+        let offset = point.location - from.location.lerp(to.location, t);
+        let distance = offset.length();
+        if distance >= point.radius {
+            return;
+        }
+        let Some(normal) = offset.try_normalize() else {
+            return;
+        };
+
+        let weights = (
+            point.get_inverse_mass(),
+            (1.0 - t) * from.get_inverse_mass(),
+            t * to.get_inverse_mass(),
+        );
+        let total_inverse_mass = weights.0 + (1.0 - t) * weights.1 + t * weights.2;
+        if total_inverse_mass == 0.0 {
+            return;
+        }
+
+        let edge_velocity = from.velocity.lerp(to.velocity, t);
+        let approach_speed = (point.velocity - edge_velocity).dot(normal).min(0.0);
+        let correction = normal * (point.radius - distance) / total_inverse_mass;
+        let impulse = normal * -approach_speed / total_inverse_mass;
 
-            let point2 = physics_system.get_point_mut(*id2).expect("Invalid id");
-            point2.velocity -= *delta_v;
+        for (id, weight, sign) in [
+            (id, weights.0, 1.0),
+            (id1, weights.1, -1.0),
+            (id2, weights.2, -1.0),
+        ] {
+            let point = physics_system.get_point_mut(id).expect("Invalid id");
+            point.location += correction * weight * sign;
+            point.velocity += impulse * weight * sign;
         }
     }
 
-    fn apply_constraints(&self, physics_system: &mut PhysicsSystem, delta: f32) {
-        let point_changes: Vec<_> = physics_system
+    fn apply_edge_collisions(&self, physics_system: &mut PhysicsSystem) {
+        for (id, id1, id2) in self.find_edge_collision_candidates(physics_system) {
+            Self::solve_edge_contact(physics_system, id, id1, id2);
+        }
+    }
+
+    fn solve_rods(&self, physics_system: &mut PhysicsSystem) {
+        let rods: Vec<_> = physics_system
             .get_constraints()
-            .par_iter()
-            .map(|c| {
+            .iter()
+            .filter(|c| c.get_kind() == ConstraintKind::Rod)
+            .map(|c| (c.get_point1(), c.get_point2(), c.get_distance()))
+            .collect();
+        if rods.is_empty() {
+            return;
+        }
+
+        for _ in 0..self.params.rod_iterations {
+            for (id1, id2, distance) in &rods {
                 let point1 = physics_system
-                    .get_point(c.get_point1())
+                    .get_point(*id1)
                     .expect("Invalid constraint: point should be some");
                 let point2 = physics_system
-                    .get_point(c.get_point2())
+                    .get_point(*id2)
                     .expect("Invalid constraint: point should be some");
 
+                let inverse_mass1 = point1.get_inverse_mass();
+                let inverse_mass2 = point2.get_inverse_mass();
+                let total_inverse_mass = inverse_mass1 + inverse_mass2;
+                if total_inverse_mass == 0.0 {
+                    continue;
+                }
+
                 let direction = point2.location - point1.location;
-                let distance_between_points = direction.length();
                 let direction_norm = direction.normalize_or_zero();
-                let displacement = distance_between_points - c.get_distance();
-                let force = self.params.spring_coeff * displacement;
-                let relative_velocity = point2.velocity - point1.velocity;
-                let damping = self.params.damping * relative_velocity.dot(direction_norm);
-                let delta_v = (force + damping) * direction_norm * delta;
-
-                let required_change = (c.get_point1(), c.get_point2(), delta_v);
-                required_change
+                let location_correction =
+                    (direction.length() - distance) / total_inverse_mass * direction_norm;
+                let velocity_correction = (point2.velocity - point1.velocity).dot(direction_norm)
+                    / total_inverse_mass
+                    * direction_norm;
+
+                let point1 = physics_system.get_point_mut(*id1).expect("Invalid id");
+                point1.location += location_correction * inverse_mass1;
+                point1.velocity += velocity_correction * inverse_mass1;
+
+                let point2 = physics_system.get_point_mut(*id2).expect("Invalid id");
+                point2.location -= location_correction * inverse_mass2;
+                point2.velocity -= velocity_correction * inverse_mass2;
+            }
+        }
+    }
+
+    fn is_constraint_broken(&self, constraint: &Constraint, from: Vec2, to: Vec2) -> bool {
+        let displacement = from.distance(to) - constraint.get_distance();
+        let displacement = if constraint.get_kind().is_slack(displacement) {
+            0.0
+        } else {
+            displacement.abs()
+        };
+        match constraint.get_break_threshold() {
+            Some(BreakThreshold::Strain(max_strain)) => {
+                displacement / constraint.get_distance() > max_strain
+            }
+            Some(BreakThreshold::Force(max_force)) => {
+                constraint.get_stiffness_or(self.params.spring_coeff) * displacement > max_force
+            }
+            None => false,
+        }
+    }
+
+    fn break_constraints(&self, physics_system: &mut PhysicsSystem) -> Vec<SimulationEvent> {
+        let broken_constraints: Vec<_> = physics_system
+            .get_constraints()
+            .par_iter()
+            .enumerate()
+            .filter_map(|(index, c)| {
+                c.get_break_threshold()?;
+                let from = physics_system
+                    .get_point(c.get_point1())
+                    .expect("Invalid constraint: point should be some")
+                    .location;
+                let to = physics_system
+                    .get_point(c.get_point2())
+                    .expect("Invalid constraint: point should be some")
+                    .location;
+
+                if self.is_constraint_broken(c, from, to) {
+                    Some((index, SimulationEvent::ConstraintBroken { from, to }))
+                } else {
+                    None
+                }
             })
             .collect();
 
-        Self::apply_point_changes(physics_system, &point_changes);
+        if broken_constraints.is_empty() {
+            return vec![];
+        }
+
+        let indices: Vec<_> = broken_constraints.iter().map(|(index, _)| *index).collect();
+        physics_system.remove_constraints_at(&indices);
+
+        broken_constraints
+            .into_iter()
+            .map(|(_index, event)| event)
+            .collect()
     }
 
-    fn calculate_collision(&self, point1: &Point, point2: &Point, delta: f32) -> Vec2 {
-        let direction = point1.location - point2.location;
-        let distance = direction.length();
-        if distance >= self.params.point_size * 2.0 {
-            return vec2(0.0, 0.0);
+    /// Spatial hash sized for the largest point, together with that point's radius
+    pub(crate) fn build_spatial_hash(physics_system: &PhysicsSystem) -> (SpatialHash, f32) {
+        let points = physics_system.get_points_ids();
+        let max_radius = points
+            .values()
+            .map(|point| point.radius)
+            .fold(f32::EPSILON, f32::max);
+
+        (
+            SpatialHash::new(max_radius * 2.0, physics_system),
+            max_radius,
+        )
+    }
+
+    fn is_overlapping(point1: &Point, point2: &Point) -> bool {
+        point1.location.distance(point2.location) < point1.radius + point2.radius
+    }
+
+    /// Pairs of overlapping points, each pair listed once with the lower id first
+    fn find_collision_pairs(&self, physics_system: &PhysicsSystem) -> Vec<(u64, u64)> {
+        let points = physics_system.get_points_ids();
+        let ids = physics_system.get_sorted_ids();
+        let pair_filter = &CollisionPairFilter::new(physics_system, &self.params);
+        match self.params.broad_phase {
+            BroadPhase::AllPairs => ids
+                .par_iter()
+                .enumerate()
+                .flat_map_iter(|(index, id)| {
+                    let point = &points[id];
+                    ids[index + 1..]
+                        .iter()
+                        .filter(move |other_id| {
+                            let other_point = &points[other_id];
+                            pair_filter.can_collide(*id, point, **other_id, other_point)
+                                && Self::is_overlapping(point, other_point)
+                        })
+                        .map(move |other_id| (*id, *other_id))
+                })
+                .collect(),
+            BroadPhase::SpatialHash => {
+                let (spatial_hash, max_radius) = Self::build_spatial_hash(physics_system);
+                ids.par_iter()
+                    .flat_map_iter(|id| {
+                        let point = &points[id];
+                        spatial_hash
+                            .get_nearby_ids(point.location, point.radius + max_radius)
+                            .filter(move |other_id| {
+                                let other_point =
+                                    points.get(other_id).expect("Point should be found");
+                                id < other_id
+                                    && pair_filter.can_collide(*id, point, *other_id, other_point)
+                                    && Self::is_overlapping(point, other_point)
+                            })
+                            .map(move |other_id| (*id, other_id))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect()
+            }
         }
+    }
 
-        let force = (self.params.point_size * 2.0 - distance) * self.params.collision_force * delta;
+    /// Moves `point1` by `correction` and `point2` against it, split by inverse mass
+    fn apply_location_correction(
+        physics_system: &mut PhysicsSystem,
+        id1: u64,
+        id2: u64,
+        correction: Vec2,
+    ) {
+        let point1 = physics_system.get_point_mut(id1).expect("Invalid id");
+        point1.location += correction * point1.get_inverse_mass();
 
-        direction.normalize_or_zero() * force
+        let point2 = physics_system.get_point_mut(id2).expect("Invalid id");
+        point2.location -= correction * point2.get_inverse_mass();
     }
 
-    fn apply_collision_velocity_changes(
+    fn get_total_inverse_mass(physics_system: &PhysicsSystem, id1: u64, id2: u64) -> f32 {
+        let point1 = physics_system.get_point(id1).expect("Invalid id");
+        let point2 = physics_system.get_point(id2).expect("Invalid id");
+
+        point1.get_inverse_mass() + point2.get_inverse_mass()
+    }
+
+    /// Single XPBD projection of a distance constraint, accumulating its Lagrange multiplier
+    fn solve_xpbd_constraint(
+        &self,
         physics_system: &mut PhysicsSystem,
-        point_changes: &[(u64, Vec2)],
+        constraint: &Constraint,
+        lambda: &mut f32,
+        delta: f32,
     ) {
-        for (id, delta_v) in point_changes {
-            let point = physics_system
-                .get_point_mut(*id)
-                .expect("Point should be found");
-            point.velocity += *delta_v;
+        let id1 = constraint.get_point1();
+        let id2 = constraint.get_point2();
+        let total_inverse_mass = Self::get_total_inverse_mass(physics_system, id1, id2);
+        if total_inverse_mass == 0.0 {
+            return;
+        }
+
+        let from = physics_system.get_point(id1).expect("Invalid id").location;
+        let to = physics_system.get_point(id2).expect("Invalid id").location;
+        let direction = to - from;
+        let displacement = direction.length() - constraint.get_distance();
+        if constraint.get_kind().is_slack(displacement) {
+            return;
         }
+
+        let compliance = match constraint.get_kind() {
+            ConstraintKind::Rod => 0.0,
+            _ => {
+                // Without stiffness the constraint carries no load, and its compliance is infinite
+                let stiffness = constraint.get_stiffness_or(self.params.spring_coeff);
+                if stiffness <= 0.0 {
+                    return;
+                }
+                1.0 / stiffness
+            }
+        } / (delta * delta);
+        let delta_lambda =
+            (-displacement - compliance * *lambda) / (total_inverse_mass + compliance);
+        *lambda += delta_lambda;
+
+        Self::apply_location_correction(
+            physics_system,
+            id1,
+            id2,
+            -direction.normalize_or_zero() * delta_lambda,
+        );
     }
 
-    fn apply_collisions(&self, physics_system: &mut PhysicsSystem, delta: f32) {
-        let point_changes: Vec<_> = physics_system
+    fn solve_contact(physics_system: &mut PhysicsSystem, id1: u64, id2: u64) {
+        let total_inverse_mass = Self::get_total_inverse_mass(physics_system, id1, id2);
+        if total_inverse_mass == 0.0 {
+            return;
+        }
+
+        let point1 = physics_system.get_point(id1).expect("Invalid id");
+        let point2 = physics_system.get_point(id2).expect("Invalid id");
+        let direction = point1.location - point2.location;
+        let overlap = point1.radius + point2.radius - direction.length();
+        if overlap <= 0.0 {
+            return;
+        }
+
+        Self::apply_location_correction(
+            physics_system,
+            id1,
+            id2,
+            direction.normalize_or_zero() * overlap / total_inverse_mass,
+        );
+    }
+
+    fn xpbd_step(&self, physics_system: &mut PhysicsSystem, delta: f32) {
+        let previous_locations: Vec<_> = physics_system
             .get_points_ids()
-            .par_iter()
-            .map(|(id, point)| {
-                let mut change = vec2(0.0, 0.0);
-                for (other_id, other_point) in physics_system.get_points_ids() {
-                    if id == other_id {
-                        continue;
-                    }
-                    change += self.calculate_collision(point, other_point, delta);
-                }
+            .iter()
+            .map(|(id, point)| (*id, point.location))
+            .collect();
 
-                (*id, change)
-            })
-            .filter(|(_id, v)| v.x != 0.0 || v.y != 0.0)
+        self.apply_force_generators(physics_system, delta);
+        self.apply_velocity(physics_system, delta);
+
+        let collision_pairs = if self.params.solve_contacts {
+            self.find_collision_pairs(physics_system)
+        } else {
+            vec![]
+        };
+        let is_solving_springs = self.params.solve_springs;
+        let constraints: Vec<_> = physics_system
+            .get_constraints()
+            .iter()
+            .filter(|c| is_solving_springs || c.get_kind() == ConstraintKind::Rod)
+            .cloned()
             .collect();
+        let mut lambdas = vec![0.0; constraints.len()];
+        for _ in 0..self.params.solver_iterations {
+            for (constraint, lambda) in constraints.iter().zip(lambdas.iter_mut()) {
+                self.solve_xpbd_constraint(physics_system, constraint, lambda, delta);
+            }
+            for (id1, id2) in &collision_pairs {
+                Self::solve_contact(physics_system, *id1, *id2);
+            }
+        }
 
-        Self::apply_collision_velocity_changes(physics_system, &point_changes);
+        for (id, previous_location) in previous_locations {
+            let point = physics_system.get_point_mut(id).expect("Invalid id");
+            if !point.is_static {
+                point.velocity = Self::calculate_velocity(previous_location, point.location, delta);
+            }
+        }
     }
 
-    pub fn next_step(&self, physics_system: &mut PhysicsSystem, delta: f32) {
-        self.apply_gravity(physics_system, delta);
-        self.apply_constraints(physics_system, delta);
-        self.apply_collisions(physics_system, delta);
+    fn semi_implicit_euler_step(&self, physics_system: &mut PhysicsSystem, delta: f32) {
+        self.apply_force_generators(physics_system, delta);
         self.apply_velocity(physics_system, delta);
-        self.fit_in_screen(physics_system);
+        self.solve_rods(physics_system);
+    }
+
+    pub fn get_integrator(&self) -> Integrator {
+        self.params.integrator
+    }
+
+    pub fn toggle_integrator(&mut self) {
+        self.params.integrator = match self.params.integrator {
+            Integrator::SemiImplicitEuler => Integrator::Xpbd,
+            Integrator::Xpbd => Integrator::SemiImplicitEuler,
+        };
+    }
+
+    pub fn get_params(&self) -> &SimulationParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: SimulationParams) {
+        self.params = params;
+    }
+
+    pub fn get_broad_phase(&self) -> BroadPhase {
+        self.params.broad_phase
+    }
+
+    pub fn toggle_skip_connected_collisions(&mut self) {
+        self.params.skip_connected_collisions = !self.params.skip_connected_collisions;
+    }
+
+    pub fn toggle_uniform_gravity(&mut self) {
+        self.params.is_uniform_gravity = !self.params.is_uniform_gravity;
+    }
+
+    pub fn cycle_n_body_mode(&mut self) {
+        self.params.n_body.mode = self.params.n_body.mode.next();
+    }
+
+    pub fn toggle_broad_phase(&mut self) {
+        self.params.broad_phase = match self.params.broad_phase {
+            BroadPhase::AllPairs => BroadPhase::SpatialHash,
+            BroadPhase::SpatialHash => BroadPhase::AllPairs,
+        };
+    }
+
+    /// Advances the simulation by `delta`, returning what happened during the step
+    pub fn next_step(
+        &self,
+        physics_system: &mut PhysicsSystem,
+        delta: f32,
+    ) -> Vec<SimulationEvent> {
+        let mut events = self.break_constraints(physics_system);
+        match self.params.integrator {
+            Integrator::SemiImplicitEuler => self.semi_implicit_euler_step(physics_system, delta),
+            Integrator::Xpbd => self.xpbd_step(physics_system, delta),
+        }
+        self.apply_edge_collisions(physics_system);
+        Self::apply_obstacle_collisions(physics_system);
+        events.extend(self.apply_walls(physics_system));
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        force_generator::{Collisions, ForceGenerator},
+        math::closest_point_on_segment,
+    };
+
+    use super::*;
+
+    const DELTA: f32 = 1.0 / 240.0;
+    const TOLERANCE: f32 = 1e-4;
+
+    fn get_simulator(broad_phase: BroadPhase) -> Simulator {
+        let wall = Wall {
+            kind: WallKind::Solid,
+            restitution: 0.3,
+            friction: 0.2,
+        };
+        let params = SimulationParams {
+            gravity: 0.09,
+            is_uniform_gravity: true,
+            n_body: NBodyParams::default(),
+            coulomb: CoulombParams::default(),
+            air_resistence: 0.96,
+            point_size: 0.015,
+            spring_coeff: 550.0,
+            damping: 6.0,
+            collision_force: 100.0,
+            skip_connected_collisions: false,
+            broad_phase,
+            rod_iterations: 8,
+            integrator: Integrator::SemiImplicitEuler,
+            solver_iterations: 10,
+            solve_springs: true,
+            solve_contacts: true,
+            parallel_stages: false,
+        };
+        let bounding_box = SimulationBoundingBox {
+            min_x: 0.0,
+            max_x: 1.0,
+            min_y: 0.0,
+            max_y: 1.0,
+            left: wall,
+            right: wall,
+            top: wall,
+            bottom: wall,
+        };
+
+        Simulator::new(params, bounding_box)
+    }
+
+    /// Jittered grid of `columns` by `columns` points with radii from 0.01 to 0.04, packed so
+    /// that many overlap
+    fn get_physics_system(columns: usize) -> PhysicsSystem {
+        let mut physics_system = PhysicsSystem::new();
+        for i in 0..columns * columns {
+            let (x, y) = ((i % columns) as f32, (i / columns) as f32);
+            let jitter = vec2((i * 7 % 11) as f32, (i * 13 % 17) as f32) * 0.001;
+            let radius = 0.01 + (i % 4) as f32 * 0.01;
+            physics_system.add_point(Point::new(
+                vec2(0.1, 0.1) + vec2(x, y) * 0.04 + jitter,
+                Vec2::ZERO,
+                i % 37 == 0,
+                radius,
+                1.0 + (i % 3) as f32,
+            ));
+        }
+
+        physics_system
+    }
+
+    fn assert_close(change1: Vec2, change2: Vec2) {
+        let scale = change1.length().max(change2.length()).max(1.0);
+        assert!(
+            change1.distance(change2) <= TOLERANCE * scale,
+            "{change1} differs from {change2}"
+        );
+    }
+
+    #[test]
+    fn broad_phases_find_the_same_collision_pairs() {
+        let physics_system = get_physics_system(20);
+        let mut all_pairs =
+            get_simulator(BroadPhase::AllPairs).find_collision_pairs(&physics_system);
+        let mut spatial_hash =
+            get_simulator(BroadPhase::SpatialHash).find_collision_pairs(&physics_system);
+        all_pairs.sort_unstable();
+        spatial_hash.sort_unstable();
+
+        assert!(!all_pairs.is_empty());
+        assert_eq!(all_pairs, spatial_hash);
+    }
+
+    #[test]
+    fn broad_phases_give_the_same_collision_velocity_changes() {
+        let physics_system = get_physics_system(20);
+        let get_changes = |broad_phase| -> HashMap<u64, Vec2> {
+            Collisions
+                .get_velocity_changes(
+                    &physics_system,
+                    get_simulator(broad_phase).get_params(),
+                    DELTA,
+                )
+                .into_iter()
+                .collect()
+        };
+        let all_pairs = get_changes(BroadPhase::AllPairs);
+        let spatial_hash = get_changes(BroadPhase::SpatialHash);
+
+        assert!(!all_pairs.is_empty());
+        assert_eq!(all_pairs.len(), spatial_hash.len());
+        for (id, change) in &all_pairs {
+            assert_close(*change, spatial_hash[id]);
+        }
+    }
+
+    #[test]
+    fn broad_phases_simulate_the_same_velocities() {
+        let mut all_pairs = get_physics_system(8);
+        let mut spatial_hash = get_physics_system(8);
+        let all_pairs_simulator = get_simulator(BroadPhase::AllPairs);
+        let spatial_hash_simulator = get_simulator(BroadPhase::SpatialHash);
+        for _ in 0..300 {
+            all_pairs_simulator.next_step(&mut all_pairs, DELTA);
+            spatial_hash_simulator.next_step(&mut spatial_hash, DELTA);
+        }
+
+        for (id, point) in all_pairs.get_points_ids() {
+            assert_close(
+                point.velocity,
+                spatial_hash.get_point(*id).unwrap().velocity,
+            );
+        }
+    }
+
+    #[test]
+    fn skip_connected_collisions_skips_only_connected_pairs() {
+        let mut simulator = get_simulator(BroadPhase::SpatialHash);
+        let mut physics_system = PhysicsSystem::new();
+        let ids: Vec<_> = [0.5, 0.53, 0.56]
+            .into_iter()
+            .map(|x| physics_system.add_point(get_loose_point(vec2(x, 0.5), Vec2::ZERO)))
+            .collect();
+        physics_system.add_constraint(Constraint::new(
+            ids[0],
+            ids[1],
+            0.03,
+            ConstraintKind::Rod,
+            None,
+            None,
+            None,
+        ));
+        let connected_pair = (ids[0], ids[1]);
+        let loose_pair = (ids[1], ids[2]);
+
+        assert_eq!(
+            simulator.find_collision_pairs(&physics_system),
+            vec![connected_pair, loose_pair]
+        );
+        simulator.toggle_skip_connected_collisions();
+        assert_eq!(
+            simulator.find_collision_pairs(&physics_system),
+            vec![loose_pair]
+        );
+    }
+
+    #[test]
+    fn edge_contact_shares_the_impulse_between_both_ends_by_t() {
+        let mut physics_system = PhysicsSystem::new();
+        let from = physics_system.add_point(get_loose_point(vec2(0.0, 0.5), Vec2::ZERO));
+        let to = physics_system.add_point(get_loose_point(vec2(1.0, 0.5), Vec2::ZERO));
+        // Touches the edge a quarter of the way along it, moving into it
+        let id = physics_system.add_point(get_loose_point(vec2(0.25, 0.49), vec2(0.0, 1.0)));
+
+        Simulator::solve_edge_contact(&mut physics_system, id, from, to);
+
+        let velocity_of = |id| physics_system.get_point(id).unwrap().velocity;
+        let (from_change, to_change) = (velocity_of(from), velocity_of(to));
+        assert!(from_change.y > 0.0);
+        assert_close(from_change, to_change * 3.0);
+        assert_close(velocity_of(id) + from_change + to_change, vec2(0.0, 1.0));
+        let edge_velocity = from_change.lerp(to_change, 0.25);
+        assert_close(velocity_of(id), edge_velocity);
+    }
+
+    #[test]
+    fn broad_phases_find_the_same_edge_collision_candidates() {
+        let mut physics_system = get_physics_system(8);
+        let ids = physics_system.get_sorted_ids().to_vec();
+        let (id1, id2) = (ids[0], ids[ids.len() - 1]);
+        let mut constraint = Constraint::new(id1, id2, 0.4, ConstraintKind::Rod, None, None, None);
+        constraint.set_colliding(true);
+        assert!(physics_system.add_constraint(constraint));
+
+        let all_pairs =
+            get_simulator(BroadPhase::AllPairs).find_edge_collision_candidates(&physics_system);
+        let spatial_hash =
+            get_simulator(BroadPhase::SpatialHash).find_edge_collision_candidates(&physics_system);
+        let touching = |candidates: Vec<(u64, u64, u64)>| -> HashSet<_> {
+            candidates
+                .into_iter()
+                .filter(|(id, id1, id2)| {
+                    let point = physics_system.get_point(*id).unwrap();
+                    let from = physics_system.get_point(*id1).unwrap().location;
+                    let to = physics_system.get_point(*id2).unwrap().location;
+                    point
+                        .location
+                        .distance(closest_point_on_segment(point.location, from, to))
+                        < point.radius
+                })
+                .collect()
+        };
+
+        let all_pairs = touching(all_pairs);
+        assert!(!all_pairs.is_empty());
+        assert_eq!(all_pairs, touching(spatial_hash));
+    }
+
+    /// Two points 0.2 apart, joined by a spring of rest length 0.1 and stiffness 100
+    fn get_stretched_spring(break_threshold: BreakThreshold) -> PhysicsSystem {
+        let mut physics_system = PhysicsSystem::new();
+        let id1 = physics_system.add_point(get_loose_point(vec2(0.3, 0.5), Vec2::ZERO));
+        let id2 = physics_system.add_point(get_loose_point(vec2(0.5, 0.5), Vec2::ZERO));
+        assert!(physics_system.add_constraint(Constraint::new(
+            id1,
+            id2,
+            0.1,
+            ConstraintKind::Spring,
+            Some(100.0),
+            None,
+            Some(break_threshold),
+        )));
+
+        physics_system
+    }
+
+    /// Whether the spring of `get_stretched_spring` breaks, checking what is reported if it does
+    fn is_spring_broken(break_threshold: BreakThreshold) -> bool {
+        let simulator = get_simulator(BroadPhase::SpatialHash);
+        let mut physics_system = get_stretched_spring(break_threshold);
+
+        let events = simulator.break_constraints(&mut physics_system);
+
+        match events[..] {
+            [] => {
+                assert_eq!(physics_system.get_constraints().len(), 1);
+                false
+            }
+            [SimulationEvent::ConstraintBroken { from, to }] => {
+                assert_eq!((from, to), (vec2(0.3, 0.5), vec2(0.5, 0.5)));
+                assert!(physics_system.get_constraints().is_empty());
+                true
+            }
+            _ => panic!("Unexpected events {events:?}"),
+        }
+    }
+
+    #[test]
+    fn strain_threshold_breaks_constraints() {
+        assert!(is_spring_broken(BreakThreshold::Strain(0.5)));
+        assert!(!is_spring_broken(BreakThreshold::Strain(2.0)));
+    }
+
+    #[test]
+    fn force_threshold_breaks_constraints() {
+        assert!(is_spring_broken(BreakThreshold::Force(5.0)));
+        assert!(!is_spring_broken(BreakThreshold::Force(20.0)));
+    }
+
+    #[test]
+    fn constraints_without_length_are_refused() {
+        let mut physics_system = PhysicsSystem::new();
+        let id1 = physics_system.add_point(get_loose_point(vec2(0.5, 0.5), Vec2::ZERO));
+        let id2 = physics_system.add_point(get_loose_point(vec2(0.5, 0.5), Vec2::ZERO));
+        let get_rod =
+            |distance| Constraint::new(id1, id2, distance, ConstraintKind::Rod, None, None, None);
+        for distance in [0.0, -0.1, f32::NAN, f32::INFINITY] {
+            assert!(!physics_system.add_constraint(get_rod(distance)));
+        }
+        assert!(physics_system.add_constraint(get_rod(0.1)));
+    }
+
+    /// Simulator whose left and right sides are of `kind`
+    fn get_walled_simulator(kind: WallKind) -> Simulator {
+        let mut simulator = get_simulator(BroadPhase::SpatialHash);
+        simulator.bounding_box.left.kind = kind;
+        simulator.bounding_box.right.kind = kind;
+        simulator
+    }
+
+    fn get_loose_point(location: Vec2, velocity: Vec2) -> Point {
+        Point::new(location, velocity, false, 0.02, 1.0)
+    }
+
+    #[test]
+    fn periodic_wall_wraps_points_to_the_opposite_side() {
+        let simulator = get_walled_simulator(WallKind::Periodic);
+        let mut physics_system = PhysicsSystem::new();
+        let velocity = vec2(-1.0, 0.3);
+        let id = physics_system.add_point(get_loose_point(vec2(-0.01, 0.5), velocity));
+
+        let events = simulator.apply_walls(&mut physics_system);
+
+        assert!(matches!(
+            events[..],
+            [SimulationEvent::PointWrapped { id: wrapped }] if wrapped == id
+        ));
+        let point = physics_system.get_point(id).unwrap();
+        assert_close(point.location, vec2(0.99, 0.5));
+        assert_eq!(point.velocity, velocity);
+    }
+
+    #[test]
+    fn deleting_wall_removes_points_fully_past_it() {
+        let simulator = get_walled_simulator(WallKind::Deleting);
+        let mut physics_system = PhysicsSystem::new();
+        let past = physics_system.add_point(get_loose_point(vec2(-0.03, 0.5), Vec2::ZERO));
+        let touching = physics_system.add_point(get_loose_point(vec2(-0.01, 0.5), Vec2::ZERO));
+        let other = physics_system.add_point(get_loose_point(vec2(0.5, 0.3), Vec2::ZERO));
+        physics_system.add_constraint(Constraint::new(
+            past,
+            other,
+            0.2,
+            ConstraintKind::Spring,
+            None,
+            None,
+            None,
+        ));
+
+        let removed: Vec<_> = simulator
+            .apply_walls(&mut physics_system)
+            .into_iter()
+            .map(|event| match event {
+                SimulationEvent::PointRemoved { id } => id,
+                _ => panic!("Unexpected event {event:?}"),
+            })
+            .collect();
+
+        assert_eq!(removed, vec![past]);
+        assert_eq!(physics_system.get_sorted_ids(), [touching, other]);
+        assert!(physics_system.get_constraints().is_empty());
     }
 }
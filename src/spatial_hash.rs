@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+
+use crate::physics_system::PhysicsSystem;
+
+const CELLS_STARTING_CAPACITY: usize = 64;
+
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<u64>>,
+}
+impl SpatialHash {
+    /// Cells list their points in id order
+    pub fn new(cell_size: f32, physics_system: &PhysicsSystem) -> Self {
+        let mut spatial_hash = Self {
+            cell_size,
+            cells: HashMap::with_capacity(CELLS_STARTING_CAPACITY),
+        };
+
+        let points = physics_system.get_points_ids();
+        for id in physics_system.get_sorted_ids() {
+            let cell = spatial_hash.get_cell(points[id].location);
+            spatial_hash.cells.entry(cell).or_default().push(*id);
+        }
+
+        spatial_hash
+    }
+
+    fn get_cell(&self, location: Vec2) -> (i32, i32) {
+        (
+            (location.x / self.cell_size).floor() as i32,
+            (location.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Ids of all points in the cells overlapping a square of half-size `radius` around `location`
+    pub fn get_nearby_ids(&self, location: Vec2, radius: f32) -> impl Iterator<Item = u64> + '_ {
+        self.get_ids_in_box(location - radius, location + radius)
+    }
+
+    /// Ids of all points in the cells overlapping the box from `min` to `max`
+    pub fn get_ids_in_box(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = u64> + '_ {
+        let (min_x, min_y) = self.get_cell(min);
+        let (max_x, max_y) = self.get_cell(max);
+
+        (min_x..=max_x)
+            .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flat_map(|ids| ids.iter().copied())
+    }
+}
@@ -1,15 +1,22 @@
 use macroquad::{
     color::Color,
     math::Vec2,
-    shapes::{draw_line, draw_rectangle},
+    shapes::{draw_line, draw_rectangle, draw_rectangle_lines},
     text::draw_text,
     time::get_fps,
 };
 
-use crate::point::Point;
+use crate::{
+    camera::Camera,
+    constraint::{BreakThreshold, Constraint, ConstraintKind},
+    controller::Tool,
+    n_body::NBodyMode,
+    point::Point,
+    simulator::{BroadPhase, Integrator},
+};
 
 const DEBUG_WINDOW_WIDTH_COEF: f32 = 8.0;
-const DEBUG_WINDOW_HEIGHT_COEF: f32 = 2.5;
+const DEBUG_WINDOW_PADDING_COEF: f32 = 0.5;
 
 pub struct UiParams {
     pub paused_text_location: (f32, f32),
@@ -33,6 +40,34 @@ pub struct UiParams {
     pub speed_text_location: (f32, f32),
     pub speed_text_size: f32,
     pub speed_text_color: Color,
+
+    pub placement_text_location: (f32, f32),
+    pub tool_text_location: (f32, f32),
+
+    pub broken_line_color: Color,
+    pub broken_line_flash_time: f32,
+}
+
+/// Settings of newly placed points and constraints
+pub struct PlacementInfo {
+    pub mass: f32,
+    pub radius: f32,
+    pub charge: f32,
+    pub stiffness: f32,
+    pub break_threshold: Option<BreakThreshold>,
+    pub constraint_kind: ConstraintKind,
+}
+
+pub struct DebugInfo {
+    pub mouse_pos: (f32, f32),
+    pub points: usize,
+    pub constraints: usize,
+    pub broad_phase: BroadPhase,
+    pub integrator: Integrator,
+    pub is_uniform_gravity: bool,
+    pub n_body_mode: NBodyMode,
+    pub is_skipping_connected_collisions: bool,
+    pub broken_constraints: usize,
 }
 
 pub struct UiRenderer {
@@ -43,24 +78,21 @@ impl UiRenderer {
         Self { params }
     }
 
-    pub fn draw_line(&self, point1: Vec2, point2: Vec2, screen_size: (f32, f32)) {
+    /// Draws a line between two world locations
+    pub fn draw_line(&self, point1: Vec2, point2: Vec2, camera: &Camera, screen_size: (f32, f32)) {
+        let from = camera.world_to_screen(point1, screen_size);
+        let to = camera.world_to_screen(point2, screen_size);
         draw_line(
-            point1.x * screen_size.0,
-            point1.y * screen_size.1,
-            point2.x * screen_size.0,
-            point2.y * screen_size.1,
-            self.params.line_size * screen_size.0.max(screen_size.1),
+            from.x,
+            from.y,
+            to.x,
+            to.y,
+            self.params.line_size * camera.get_scale(screen_size),
             self.params.line_color,
         );
     }
 
-    pub fn draw_debug_text(
-        &self,
-        screen_size: (f32, f32),
-        mouse_pos: (f32, f32),
-        points: usize,
-        constraints: usize,
-    ) {
+    pub fn draw_debug_text(&self, screen_size: (f32, f32), debug_info: &DebugInfo) {
         draw_text(
             &format!("FPS:{}", get_fps()),
             self.params.debug_text_location.0 * screen_size.0,
@@ -69,7 +101,10 @@ impl UiRenderer {
             self.params.debug_text_color,
         );
         draw_text(
-            &format!("X:{:.3} Y:{:.3}", mouse_pos.0, mouse_pos.1,),
+            &format!(
+                "X:{:.3} Y:{:.3}",
+                debug_info.mouse_pos.0, debug_info.mouse_pos.1,
+            ),
             self.params.debug_text_location.0 * screen_size.0,
             self.params.debug_text_location.1 * screen_size.1
                 + self.params.debug_text_size * screen_size.1,
@@ -77,13 +112,59 @@ impl UiRenderer {
             self.params.debug_text_color,
         );
         draw_text(
-            &format!("points:{} constraints:{}", points, constraints),
+            &format!(
+                "points:{} constraints:{}",
+                debug_info.points, debug_info.constraints
+            ),
             self.params.debug_text_location.0 * screen_size.0,
             self.params.debug_text_location.1 * screen_size.1
                 + self.params.debug_text_size * screen_size.1 * 2.0,
             self.params.debug_text_size * screen_size.0.min(screen_size.1),
             self.params.debug_text_color,
         );
+        draw_text(
+            &format!(
+                "broad phase:{:?} integrator:{:?}",
+                debug_info.broad_phase, debug_info.integrator
+            ),
+            self.params.debug_text_location.0 * screen_size.0,
+            self.params.debug_text_location.1 * screen_size.1
+                + self.params.debug_text_size * screen_size.1 * 3.0,
+            self.params.debug_text_size * screen_size.0.min(screen_size.1),
+            self.params.debug_text_color,
+        );
+        draw_text(
+            &format!(
+                "broken constraints:{} connected collisions:{}",
+                debug_info.broken_constraints,
+                if debug_info.is_skipping_connected_collisions {
+                    "off"
+                } else {
+                    "on"
+                }
+            ),
+            self.params.debug_text_location.0 * screen_size.0,
+            self.params.debug_text_location.1 * screen_size.1
+                + self.params.debug_text_size * screen_size.1 * 4.0,
+            self.params.debug_text_size * screen_size.0.min(screen_size.1),
+            self.params.debug_text_color,
+        );
+        draw_text(
+            &format!(
+                "uniform gravity:{} n-body:{:?}",
+                if debug_info.is_uniform_gravity {
+                    "on"
+                } else {
+                    "off"
+                },
+                debug_info.n_body_mode
+            ),
+            self.params.debug_text_location.0 * screen_size.0,
+            self.params.debug_text_location.1 * screen_size.1
+                + self.params.debug_text_size * screen_size.1 * 5.0,
+            self.params.debug_text_size * screen_size.0.min(screen_size.1),
+            self.params.debug_text_color,
+        );
     }
 
     pub fn draw_paused_text(&self, screen_size: (f32, f32)) {
@@ -96,36 +177,107 @@ impl UiRenderer {
         );
     }
 
-    pub fn draw_point_info(&self, screen_size: (f32, f32), id: u64, point: &Point, at: (f32, f32)) {
-        let line_to = point.velocity.normalize_or_zero()
-            * self.params.debug_point_velocity_line_length
-            + point.location;
+    /// Draws the info box at `at` in screen pixels, next to the velocity of the point
+    pub fn draw_point_info(
+        &self,
+        screen_size: (f32, f32),
+        camera: &Camera,
+        id: u64,
+        point: &Point,
+        at: Vec2,
+    ) {
+        let line_from = camera.world_to_screen(point.location, screen_size);
+        let line_to = line_from
+            + point.velocity.normalize_or_zero()
+                * self.params.debug_point_velocity_line_length
+                * camera.get_scale(screen_size);
+
+        draw_line(
+            line_from.x,
+            line_from.y,
+            line_to.x,
+            line_to.y,
+            self.params.debug_point_velocity_line_size * screen_size.0.max(screen_size.1),
+            self.params.debug_point_velocity_line_color,
+        );
+
+        self.draw_info_box(
+            screen_size,
+            at,
+            &[
+                format!(
+                    "id:{} X:{:.3} Y:{:.3}",
+                    id, point.location.x, point.location.y
+                ),
+                format!("VX:{:.3} VY:{:.3}", point.velocity.x, point.velocity.y),
+                format!(
+                    "mass:{:.2} radius:{:.3} charge:{:+.1}",
+                    point.mass, point.radius, point.charge
+                ),
+                format!(
+                    "layer:{:#x} mask:{:#x} group:{}",
+                    point.collision_filter.layer,
+                    point.collision_filter.mask,
+                    point.collision_filter.group
+                ),
+            ],
+        );
+    }
+
+    /// Stress is the length change relative to the rest length, positive when stretched
+    pub fn draw_constraint_info(
+        &self,
+        screen_size: (f32, f32),
+        constraint: &Constraint,
+        current_length: f32,
+        at: Vec2,
+    ) {
+        let rest_length = constraint.get_distance();
+        let stress = (current_length - rest_length) / rest_length;
+
+        self.draw_info_box(
+            screen_size,
+            at,
+            &[
+                format!(
+                    "{:?} {}-{}",
+                    constraint.get_kind(),
+                    constraint.get_point1(),
+                    constraint.get_point2()
+                ),
+                format!("rest:{:.3} current:{:.3}", rest_length, current_length),
+                format!("stress:{:+.1}%", stress * 100.0),
+                format!(
+                    "solid edge:{}",
+                    if constraint.is_colliding() {
+                        "yes"
+                    } else {
+                        "no"
+                    }
+                ),
+            ],
+        );
+    }
 
+    /// Draws `lines` in a box at `at` in screen pixels, kept inside the window
+    fn draw_info_box(&self, screen_size: (f32, f32), at: Vec2, lines: &[String]) {
         let rect_width =
             self.params.debug_point_text_size * DEBUG_WINDOW_WIDTH_COEF * screen_size.0;
-        let rect_height =
-            self.params.debug_point_text_size * DEBUG_WINDOW_HEIGHT_COEF * screen_size.1;
+        let rect_height = self.params.debug_point_text_size
+            * (lines.len() as f32 + DEBUG_WINDOW_PADDING_COEF)
+            * screen_size.1;
 
-        let origin_x = if at.0 * screen_size.0 + rect_width > screen_size.0 {
+        let origin_x = if at.x + rect_width > screen_size.0 {
             screen_size.0 - rect_width
         } else {
-            at.0 * screen_size.0
+            at.x
         };
-        let origin_y = if at.1 * screen_size.1 + rect_height > screen_size.1 {
+        let origin_y = if at.y + rect_height > screen_size.1 {
             screen_size.1 - rect_height
         } else {
-            at.1 * screen_size.1
+            at.y
         };
 
-        draw_line(
-            point.location.x * screen_size.0,
-            point.location.y * screen_size.1,
-            line_to.x * screen_size.0,
-            line_to.y * screen_size.1,
-            self.params.debug_point_velocity_line_size * screen_size.0.max(screen_size.1),
-            self.params.debug_point_velocity_line_color,
-        );
-
         draw_rectangle(
             origin_x,
             origin_y,
@@ -134,33 +286,137 @@ impl UiRenderer {
             self.params.debug_point_box_color,
         );
 
+        for (index, line) in lines.iter().enumerate() {
+            draw_text(
+                line,
+                origin_x + self.params.debug_point_text_size * 0.2 * screen_size.0,
+                origin_y + self.params.debug_point_text_size * screen_size.1 * (index + 1) as f32,
+                self.params.debug_point_text_size * screen_size.0.min(screen_size.1),
+                self.params.debug_point_text_color,
+            );
+        }
+    }
+
+    pub fn draw_simulation_speed(&self, screen_size: (f32, f32), speed: f32) {
+        draw_text(
+            &format!("Speed: X{:.2}", speed),
+            self.params.speed_text_location.0 * screen_size.0,
+            self.params.speed_text_location.1 * screen_size.1,
+            self.params.speed_text_size * screen_size.0.min(screen_size.1),
+            self.params.speed_text_color,
+        );
+    }
+
+    pub fn draw_point_placement(&self, screen_size: (f32, f32), placement: &PlacementInfo) {
+        let break_text = match placement.break_threshold {
+            Some(BreakThreshold::Strain(strain)) => format!("{:.0}%", strain * 100.0),
+            Some(BreakThreshold::Force(force)) => format!("{:.1}F", force),
+            None => "none".to_owned(),
+        };
         draw_text(
             &format!(
-                "id:{} X:{:.3} Y:{:.3}",
-                id, point.location.x, point.location.y
+                "Mass: {:.2} Radius: {:.3} Charge: {:+.1} {:?} Stiffness: {:.0} Break: {}",
+                placement.mass,
+                placement.radius,
+                placement.charge,
+                placement.constraint_kind,
+                placement.stiffness,
+                break_text
             ),
-            origin_x + self.params.debug_point_text_size * 0.2 * screen_size.0,
-            origin_y + self.params.debug_point_text_size * screen_size.1,
-            self.params.debug_point_text_size * screen_size.0.min(screen_size.1),
-            self.params.debug_point_text_color,
+            self.params.placement_text_location.0 * screen_size.0,
+            self.params.placement_text_location.1 * screen_size.1,
+            self.params.speed_text_size * screen_size.0.min(screen_size.1),
+            self.params.speed_text_color,
         );
+    }
 
-        draw_text(
-            &format!("VX:{:.3} VY:{:.3}", point.velocity.x, point.velocity.y),
-            origin_x + self.params.debug_point_text_size * 0.2 * screen_size.0,
-            origin_y + self.params.debug_point_text_size * screen_size.1 * 2.0,
-            self.params.debug_point_text_size * screen_size.0.min(screen_size.1),
-            self.params.debug_point_text_color,
+    /// Draws the outline of a marquee between two world locations
+    pub fn draw_selection_rectangle(
+        &self,
+        corner1: Vec2,
+        corner2: Vec2,
+        camera: &Camera,
+        screen_size: (f32, f32),
+    ) {
+        let corner1 = camera.world_to_screen(corner1, screen_size);
+        let corner2 = camera.world_to_screen(corner2, screen_size);
+        let origin = corner1.min(corner2);
+        let size = (corner2 - corner1).abs();
+
+        draw_rectangle_lines(
+            origin.x,
+            origin.y,
+            size.x,
+            size.y,
+            self.params.line_size * camera.get_scale(screen_size),
+            self.params.line_color,
         );
     }
 
-    pub fn draw_simulation_speed(&self, screen_size: (f32, f32), speed: f32) {
+    /// Shows the active tool, with the prefab settings when placing prefabs
+    pub fn draw_tool(
+        &self,
+        screen_size: (f32, f32),
+        tool: Tool,
+        segments: usize,
+        is_pinned: bool,
+        force_field_strength_scale: f32,
+        is_edge_colliding: bool,
+    ) {
+        let tool_text = match tool {
+            Tool::Point => "Tool: Point".to_owned(),
+            Tool::Prefab(prefab) => format!(
+                "Tool: {:?} Segments: {} Pinned: {}",
+                prefab,
+                segments,
+                if is_pinned { "yes" } else { "no" }
+            ),
+            Tool::Obstacle(obstacle) => format!("Tool: {:?} obstacle", obstacle),
+            Tool::ForceField(force_field) => format!(
+                "Tool: {:?} Strength: x{}",
+                force_field, force_field_strength_scale
+            ),
+        };
+        let text = format!(
+            "{} Solid edges: {}",
+            tool_text,
+            if is_edge_colliding { "yes" } else { "no" }
+        );
         draw_text(
-            &format!("Speed: X{:.2}", speed),
-            self.params.speed_text_location.0 * screen_size.0,
-            self.params.speed_text_location.1 * screen_size.1,
+            &text,
+            self.params.tool_text_location.0 * screen_size.0,
+            self.params.tool_text_location.1 * screen_size.1,
             self.params.speed_text_size * screen_size.0.min(screen_size.1),
             self.params.speed_text_color,
         );
     }
+
+    pub fn get_broken_constraint_flash_time(&self) -> f32 {
+        self.params.broken_line_flash_time
+    }
+
+    /// Fades out the line of a broken constraint over `broken_line_flash_time`
+    pub fn draw_broken_constraint(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        time_left: f32,
+        camera: &Camera,
+        screen_size: (f32, f32),
+    ) {
+        let intensity = (time_left / self.params.broken_line_flash_time).clamp(0.0, 1.0);
+        let mut color = self.params.broken_line_color;
+        color.a *= intensity;
+
+        let from = camera.world_to_screen(from, screen_size);
+        let to = camera.world_to_screen(to, screen_size);
+        draw_line(
+            from.x,
+            from.y,
+            to.x,
+            to.y,
+            self.params.line_size * camera.get_scale(screen_size),
+            color,
+        );
+    }
 }